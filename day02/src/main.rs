@@ -1,11 +1,11 @@
-use intcode::Intcode;
-use std::fs;
+use intcode::instruction_set::InstructionSet;
+use intcode::{Intcode, Program};
 
 fn part_1(program: &[isize]) {
     let mut intcode = Intcode::new(program);
     intcode.write_to_memory(1, 12);
     intcode.write_to_memory(2, 2);
-    intcode.run();
+    intcode.run().unwrap();
 
     assert_eq!(intcode.read_from_memory(0), 3_306_701);
 }
@@ -19,7 +19,7 @@ fn part_2(program: &[isize]) {
             intcode.write_to_memory(1, noun);
             intcode.write_to_memory(2, verb);
 
-            intcode.run();
+            intcode.run().unwrap();
 
             if intcode.read_from_memory(0) == EXPECTED {
                 assert_eq!(100 * noun + verb, 7_621);
@@ -30,13 +30,9 @@ fn part_2(program: &[isize]) {
 }
 
 fn main() {
-    let content = fs::read_to_string("input").expect("file not found");
-    let content = content.trim();
+    let program = Program::load("input").unwrap();
 
-    let program: Vec<_> = content
-        .split(',')
-        .map(|value| value.parse::<isize>().unwrap())
-        .collect();
+    InstructionSet::day02().validate(&program).unwrap();
 
     part_1(&program);
     part_2(&program);
@@ -1,6 +1,5 @@
-use intcode::Intcode;
+use intcode::{Intcode, Program};
 use std::collections::{HashMap, HashSet};
-use std::fs;
 
 enum Direction {
     Up,
@@ -42,7 +41,7 @@ impl Robot {
             };
             self.intcode.add_input(input);
 
-            self.intcode.run();
+            self.intcode.run().unwrap();
             let output_1 = self.intcode.get_first_output().unwrap();
             let output_2 = self.intcode.get_first_output().unwrap();
 
@@ -140,13 +139,7 @@ fn part_2(program: &[isize]) {
 }
 
 fn main() {
-    let content = fs::read_to_string("input").expect("file not found");
-    let content = content.trim();
-
-    let program: Vec<_> = content
-        .split(',')
-        .map(|value| value.parse::<isize>().unwrap())
-        .collect();
+    let program = Program::load("input").unwrap();
 
     part_1(&program);
     part_2(&program);
@@ -1,10 +1,9 @@
-use intcode::Intcode;
+use intcode::{Intcode, Program};
 //use std::collections::{HashMap, HashSet};
-use std::fs;
 
 fn part_1(program: &[isize]) {
     let mut intcode = Intcode::new(&program);
-    intcode.run();
+    intcode.run().unwrap();
 
     let output = intcode.get_output();
     let block_count = output
@@ -37,7 +36,7 @@ fn part_2(program: &[isize]) {
     let mut score = 0;
 
     while !intcode.finished() {
-        intcode.run();
+        intcode.run().unwrap();
 
         while intcode.has_output() {
             let tile_type = intcode.get_last_output().unwrap();
@@ -63,13 +62,7 @@ fn part_2(program: &[isize]) {
 }
 
 fn main() {
-    let content = fs::read_to_string("input").expect("file not found");
-    let content = content.trim();
-
-    let program: Vec<_> = content
-        .split(',')
-        .map(|value| value.parse::<isize>().unwrap())
-        .collect();
+    let program = Program::load("input").unwrap();
 
     part_1(&program);
     part_2(&program);
@@ -1,6 +1,5 @@
-use intcode::Intcode;
+use intcode::{Intcode, Program};
 use std::collections::{HashMap, VecDeque};
-use std::fs;
 
 enum Direction {
     Up,
@@ -51,7 +50,7 @@ impl Droid {
                 self.intcode.add_input(input);
             }
 
-            self.intcode.run();
+            self.intcode.run().unwrap();
 
             if let Some(output) = self.intcode.get_last_output() {
                 match output {
@@ -279,13 +278,7 @@ fn part_2(program: &[isize]) {
 }
 
 fn main() {
-    let content = fs::read_to_string("input").expect("file not found");
-    let content = content.trim();
-
-    let program: Vec<_> = content
-        .split(',')
-        .map(|value| value.parse::<isize>().unwrap())
-        .collect();
+    let program = Program::load("input").unwrap();
 
     part_1(&program);
     part_2(&program);
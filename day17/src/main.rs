@@ -1,5 +1,4 @@
-use intcode::Intcode;
-use std::fs;
+use intcode::{Intcode, Program};
 
 struct Robot {
     intcode: Intcode,
@@ -13,38 +12,20 @@ impl Robot {
     }
 
     fn run(&mut self) {
-        // A, B, A, B, A, C, A, C, B, C
-        let main_routine = vec![
-            65, 44, 66, 44, 65, 44, 66, 44, 65, 44, 67, 44, 65, 44, 67, 44, 66, 44, 67, 10,
-        ];
-        // R6, L10, R10, R10
-        let fun_a = vec![
-            82, 44, 54, 44, 76, 44, 49, 48, 44, 82, 44, 49, 48, 44, 82, 44, 49, 48, 10,
-        ];
-        // L10, L12, R10
-        let fun_b = vec![76, 44, 49, 48, 44, 76, 44, 49, 50, 44, 82, 44, 49, 48, 10];
-        // R6, L12, L10
-        let fun_c = vec![82, 44, 54, 44, 76, 44, 49, 50, 44, 76, 44, 49, 48, 10];
+        let main_routine = "A,B,A,B,A,C,A,C,B,C";
+        let fun_a = "R,6,L,10,R,10,R,10";
+        let fun_b = "L,10,L,12,R,10";
+        let fun_c = "R,6,L,12,L,10";
 
-        main_routine
-            .iter()
-            .chain(fun_a.iter())
-            .chain(fun_b.iter())
-            .chain(fun_c.iter())
-            .for_each(|i| self.intcode.add_input(*i));
-
-        self.intcode.add_input(110);
-        self.intcode.add_input(10);
+        for line in &[main_routine, fun_a, fun_b, fun_c, "n"] {
+            self.intcode.send_line(line);
+        }
 
         self.intcode.write_to_memory(0, 2);
-        self.intcode.run();
+        self.intcode.run().unwrap();
 
-        let output_char: Vec<char> = self
-            .intcode
-            .get_output()
-            .iter()
-            .map(|value| *value as u8 as char)
-            .collect();
+        let output = self.intcode.take_ascii_output();
+        let output_char: Vec<char> = output.text.chars().collect();
         let mut grid: Vec<Vec<char>> = Vec::new();
         for row in output_char.split(|o| *o == '\n') {
             grid.push(row.to_vec());
@@ -72,18 +53,12 @@ impl Robot {
         }
 
         assert_eq!(sum, 3_660);
-        assert_eq!(self.intcode.get_output().back().unwrap(), &962_913);
+        assert_eq!(output.values, vec![962_913]);
     }
 }
 
 fn main() {
-    let content = fs::read_to_string("input").expect("file not found");
-    let content = content.trim();
-
-    let program: Vec<_> = content
-        .split(',')
-        .map(|value| value.parse::<isize>().unwrap())
-        .collect();
+    let program = Program::load("input").unwrap();
 
     let mut robot = Robot::new(&program);
     robot.run();
@@ -1,5 +1,4 @@
-use intcode::Intcode;
-use std::fs;
+use intcode::{Intcode, Program};
 
 fn part_1(program: &[isize]) {
     let mut sum = 0;
@@ -10,7 +9,7 @@ fn part_1(program: &[isize]) {
             intcode.add_input(x);
             intcode.add_input(y);
 
-            intcode.run();
+            intcode.run().unwrap();
 
             sum += intcode.get_first_output().unwrap();
         }
@@ -30,7 +29,7 @@ fn part_2(program: &[isize]) {
             let mut intcode = Intcode::new(program);
             intcode.add_input(x);
             intcode.add_input(current_y);
-            intcode.run();
+            intcode.run().unwrap();
 
             if intcode.get_first_output().unwrap() == 1 {
                 current_x = x;
@@ -42,7 +41,7 @@ fn part_2(program: &[isize]) {
             let mut intcode = Intcode::new(program);
             intcode.add_input(x);
             intcode.add_input(y);
-            intcode.run();
+            intcode.run().unwrap();
             intcode.get_first_output().unwrap()
         };
         let bottom_left = calculate_on_pos(current_x, current_y);
@@ -59,13 +58,7 @@ fn part_2(program: &[isize]) {
 }
 
 fn main() {
-    let content = fs::read_to_string("input").expect("file not found");
-    let content = content.trim();
-
-    let program: Vec<_> = content
-        .split(',')
-        .map(|value| value.parse::<isize>().unwrap())
-        .collect();
+    let program = Program::load("input").unwrap();
 
     part_1(&program);
     part_2(&program);
@@ -0,0 +1,77 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use intcode::{CachedIntcode, Intcode};
+
+fn parse(content: &str) -> Vec<isize> {
+    content
+        .trim()
+        .split(',')
+        .map(|value| value.parse::<isize>().unwrap())
+        .collect()
+}
+
+fn day09_boost(c: &mut Criterion) {
+    let program = parse(include_str!("../../day09/input"));
+    let mut group = c.benchmark_group("day09 BOOST");
+
+    group.bench_function("interpreter", |b| {
+        b.iter(|| {
+            let mut intcode = Intcode::new(&program);
+            intcode.add_input(2);
+            intcode.run().unwrap();
+            black_box(intcode.get_last_output())
+        })
+    });
+
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            let mut intcode = CachedIntcode::new(&program);
+            intcode.add_input(2);
+            intcode.run().unwrap();
+            black_box(intcode.get_last_output())
+        })
+    });
+
+    group.finish();
+}
+
+fn day19_scan(c: &mut Criterion) {
+    let program = parse(include_str!("../../day19/input"));
+    let mut group = c.benchmark_group("day19 beam scan");
+
+    group.bench_function("interpreter", |b| {
+        b.iter(|| {
+            let mut affected = 0;
+            for y in 0..50 {
+                for x in 0..50 {
+                    let mut intcode = Intcode::new(&program);
+                    intcode.add_input(x);
+                    intcode.add_input(y);
+                    intcode.run().unwrap();
+                    affected += intcode.get_first_output().unwrap();
+                }
+            }
+            black_box(affected)
+        })
+    });
+
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            let mut affected = 0;
+            for y in 0..50 {
+                for x in 0..50 {
+                    let mut intcode = CachedIntcode::new(&program);
+                    intcode.add_input(x);
+                    intcode.add_input(y);
+                    intcode.run().unwrap();
+                    affected += intcode.get_first_output().unwrap();
+                }
+            }
+            black_box(affected)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, day09_boost, day19_scan);
+criterion_main!(benches);
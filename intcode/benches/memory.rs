@@ -0,0 +1,76 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use intcode::memory::Memory;
+use intcode::Intcode;
+use std::collections::HashMap;
+
+fn parse(content: &str) -> Vec<isize> {
+    content
+        .trim()
+        .split(',')
+        .map(|value| value.parse::<isize>().unwrap())
+        .collect()
+}
+
+fn backends(c: &mut Criterion) {
+    let program = parse(include_str!("../../day09/input"));
+    let mut group = c.benchmark_group("memory backend");
+
+    group.bench_function("hashmap", |b| {
+        b.iter(|| {
+            let mut memory = HashMap::new();
+            for (address, value) in program.iter().enumerate() {
+                memory.insert(address, *value);
+            }
+            let mut sum = 0;
+            for address in 0..2 * program.len() {
+                sum += *memory.entry(address).or_insert(0);
+                memory.insert(address, sum);
+            }
+            black_box(sum)
+        })
+    });
+
+    group.bench_function("paged vector", |b| {
+        b.iter(|| {
+            let mut memory = Memory::new(&program);
+            let mut sum = 0;
+            for address in 0..2 * program.len() {
+                sum += memory.get(address);
+                memory.set(address, sum);
+            }
+            black_box(sum)
+        })
+    });
+
+    group.finish();
+}
+
+fn day09_boost(c: &mut Criterion) {
+    let program = parse(include_str!("../../day09/input"));
+
+    c.bench_function("day09 BOOST self-test", |b| {
+        b.iter(|| {
+            let mut intcode = Intcode::new(&program);
+            intcode.add_input(1);
+            intcode.run().unwrap();
+            black_box(intcode.get_last_output())
+        })
+    });
+}
+
+fn day19_probe(c: &mut Criterion) {
+    let program = parse(include_str!("../../day19/input"));
+
+    c.bench_function("day19 beam probe", |b| {
+        b.iter(|| {
+            let mut intcode = Intcode::new(&program);
+            intcode.add_input(black_box(25));
+            intcode.add_input(black_box(30));
+            intcode.run().unwrap();
+            black_box(intcode.get_first_output())
+        })
+    });
+}
+
+criterion_group!(benches, backends, day09_boost, day19_probe);
+criterion_main!(benches);
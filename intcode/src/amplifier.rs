@@ -0,0 +1,229 @@
+use crate::threaded::{ThreadedNetwork, Topology};
+use crate::{Budget, Intcode, IntcodeError, StepOutcome};
+use std::error::Error;
+use std::fmt;
+use std::thread;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wiring {
+    /// The signal passes every stage once.
+    Serial,
+    /// The last stage feeds back into the first until every stage halts.
+    Feedback,
+}
+
+#[derive(Debug)]
+pub enum AmplifierError {
+    Intcode {
+        stage: usize,
+        error: IntcodeError,
+    },
+    /// A stage stopped without producing a signal for the next one.
+    NoOutput(usize),
+    WrongPhaseCount {
+        expected: usize,
+        found: usize,
+    },
+    /// The chain has no stages to run.
+    NoStages,
+    /// A stage used up its budget before halting.
+    BudgetExhausted(usize),
+}
+
+impl fmt::Display for AmplifierError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmplifierError::Intcode { stage, error } => write!(f, "stage {}: {}", stage, error),
+            AmplifierError::NoOutput(stage) => write!(f, "stage {} produced no output", stage),
+            AmplifierError::WrongPhaseCount { expected, found } => {
+                write!(f, "expected {} phases, found {}", expected, found)
+            }
+            AmplifierError::NoStages => write!(f, "amplifier chain has no stages"),
+            AmplifierError::BudgetExhausted(stage) => {
+                write!(f, "stage {} ran out of budget", stage)
+            }
+        }
+    }
+}
+
+impl Error for AmplifierError {}
+
+/// Every ordered selection of `count` distinct values.
+pub fn permutations(values: &[isize], count: usize) -> Vec<Vec<isize>> {
+    fn extend(
+        output: &mut Vec<Vec<isize>>,
+        chosen: &mut Vec<isize>,
+        remaining: &mut Vec<isize>,
+        count: usize,
+    ) {
+        if chosen.len() == count {
+            output.push(chosen.clone());
+            return;
+        }
+        for i in 0..remaining.len() {
+            let value = remaining.remove(i);
+            chosen.push(value);
+            extend(output, chosen, remaining, count);
+            chosen.pop();
+            remaining.insert(i, value);
+        }
+    }
+
+    let mut output = Vec::new();
+    if count <= values.len() {
+        extend(&mut output, &mut Vec::new(), &mut values.to_vec(), count);
+    }
+    output
+}
+
+/// A chain of amplifiers running copies of the same program, as in day 7.
+/// Each stage first reads its phase setting, then the signal from the
+/// previous stage. Stages run on a `ThreadedNetwork`, wired as a pipeline or
+/// a ring.
+#[derive(Clone, Debug)]
+pub struct AmplifierChain {
+    program: Vec<isize>,
+    stages: usize,
+    wiring: Wiring,
+    signal: isize,
+    budget: Budget,
+}
+
+impl AmplifierChain {
+    pub fn new(program: &[isize], stages: usize) -> Self {
+        Self {
+            program: program.to_vec(),
+            stages,
+            wiring: Wiring::Serial,
+            signal: 0,
+            budget: Budget::default(),
+        }
+    }
+
+    pub fn with_wiring(self, wiring: Wiring) -> Self {
+        Self { wiring, ..self }
+    }
+
+    /// Sets the signal fed into the first stage, 0 by default.
+    pub fn with_signal(self, signal: isize) -> Self {
+        Self { signal, ..self }
+    }
+
+    /// Limits every stage of every run, see `ThreadedNetwork::with_budget`.
+    /// With a deadline, a feedback program that never halts, or whose stages
+    /// all wait on each other, fails with `AmplifierError::BudgetExhausted`
+    /// instead of hanging.
+    pub fn with_budget(self, budget: Budget) -> Self {
+        Self { budget, ..self }
+    }
+
+    pub fn stages(&self) -> usize {
+        self.stages
+    }
+
+    /// Returns the last signal leaving the final stage.
+    pub fn run(&self, phases: &[isize]) -> Result<isize, AmplifierError> {
+        if self.stages == 0 {
+            return Err(AmplifierError::NoStages);
+        }
+        if phases.len() != self.stages {
+            return Err(AmplifierError::WrongPhaseCount {
+                expected: self.stages,
+                found: phases.len(),
+            });
+        }
+
+        let topology = match self.wiring {
+            Wiring::Serial => Topology::Pipeline(self.stages),
+            Wiring::Feedback => Topology::Ring(self.stages),
+        };
+        let amplifiers = vec![Intcode::new(&self.program); self.stages];
+        let mut network = ThreadedNetwork::new(amplifiers, &topology)
+            .expect("a chain topology has one node per stage")
+            .with_budget(self.budget);
+        for (stage, phase) in phases.iter().enumerate() {
+            network.add_input(stage, *phase);
+        }
+        network.add_input(0, self.signal);
+
+        let results = network.run();
+        for (stage, result) in results.iter().enumerate() {
+            match result.outcome {
+                Err(error) => return Err(AmplifierError::Intcode { stage, error }),
+                Ok(StepOutcome::BudgetExhausted) => {
+                    return Err(AmplifierError::BudgetExhausted(stage))
+                }
+                Ok(_) => (),
+            }
+        }
+        let last = self.stages - 1;
+        results[last]
+            .outputs
+            .last()
+            .copied()
+            .ok_or(AmplifierError::NoOutput(last))
+    }
+
+    /// Tries every ordering of `phases` over the stages, spread across all
+    /// cores, and returns the settings giving the strongest signal.
+    pub fn search(&self, phases: &[isize]) -> Result<Option<(Vec<isize>, isize)>, AmplifierError> {
+        if self.stages == 0 {
+            return Err(AmplifierError::NoStages);
+        }
+        let candidates = permutations(phases, self.stages);
+        if candidates.is_empty() {
+            return Ok(None);
+        }
+
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = candidates.len().div_ceil(threads);
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = candidates
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut best: Option<(&Vec<isize>, isize)> = None;
+                        for phases in chunk {
+                            let signal = self.run(phases)?;
+                            if best.is_none_or(|(_, best)| signal > best) {
+                                best = Some((phases, signal));
+                            }
+                        }
+                        Ok(best)
+                    })
+                })
+                .collect();
+
+            let mut best: Option<(&Vec<isize>, isize)> = None;
+            for handle in handles {
+                if let Some((phases, signal)) = handle.join().expect("search thread panicked")? {
+                    if best.is_none_or(|(_, best)| signal > best) {
+                        best = Some((phases, signal));
+                    }
+                }
+            }
+            Ok(best.map(|(phases, signal)| (phases.clone(), signal)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn deadline_stops_stages_waiting_on_each_other() {
+        // Reads three values but never writes, so both stages end up waiting.
+        let program = [3, 20, 3, 20, 3, 20, 99];
+        let chain = AmplifierChain::new(&program, 2)
+            .with_wiring(Wiring::Feedback)
+            .with_budget(Budget::timeout(Duration::from_millis(200)));
+
+        match chain.run(&[0, 1]) {
+            Err(AmplifierError::BudgetExhausted(_)) => (),
+            result => panic!("expected an exhausted budget, got {:?}", result),
+        }
+    }
+}
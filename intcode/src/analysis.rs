@@ -0,0 +1,343 @@
+use crate::disassembler::{decode_at, Line, Operand};
+use crate::instruction::{Mode, Opcode};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+/// How control leaves a basic block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Terminator {
+    /// Runs into the next block.
+    Fallthrough,
+    /// Conditional jump, `None` if the target is computed.
+    Branch {
+        target: Option<usize>,
+    },
+    /// Unconditional jump, `None` if the target is computed.
+    Jump {
+        target: Option<usize>,
+    },
+    /// Jump that stored a return address on the stack first, `None` if the
+    /// callee is computed.
+    Call {
+        target: Option<usize>,
+        return_to: usize,
+    },
+    /// Jump through the return address on the stack.
+    Return,
+    Halt,
+    /// Runs into words that do not decode.
+    Invalid,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeKind {
+    Fallthrough,
+    Taken,
+    Jump,
+    Call,
+    /// From a call site to where the callee returns.
+    AfterCall,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    pub lines: Vec<Line>,
+    pub terminator: Terminator,
+}
+
+impl BasicBlock {
+    pub fn successors(&self) -> Vec<(usize, EdgeKind)> {
+        match self.terminator {
+            Terminator::Fallthrough => vec![(self.end, EdgeKind::Fallthrough)],
+            Terminator::Branch { target } => target
+                .map(|target| (target, EdgeKind::Taken))
+                .into_iter()
+                .chain(Some((self.end, EdgeKind::Fallthrough)))
+                .collect(),
+            Terminator::Jump { target } => target
+                .map(|target| (target, EdgeKind::Jump))
+                .into_iter()
+                .collect(),
+            Terminator::Call { target, return_to } => target
+                .map(|target| (target, EdgeKind::Call))
+                .into_iter()
+                .chain(Some((return_to, EdgeKind::AfterCall)))
+                .collect(),
+            Terminator::Return | Terminator::Halt | Terminator::Invalid => Vec::new(),
+        }
+    }
+}
+
+/// Control-flow graph recovered by following every statically known jump
+/// from address 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    /// Entry point and every call target.
+    pub functions: BTreeSet<usize>,
+    /// Addresses control can reach that do not hold a valid instruction.
+    pub invalid: BTreeSet<usize>,
+    size: usize,
+}
+
+/// Value an instruction writes if it only depends on immediate operands and
+/// does not overflow.
+fn constant_write(opcode: Opcode, operands: &[Operand]) -> Option<isize> {
+    match operands {
+        [a, b, _] if a.mode == Mode::Immediate && b.mode == Mode::Immediate => match opcode {
+            Opcode::Add => a.value.checked_add(b.value),
+            Opcode::Mul => a.value.checked_mul(b.value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Calling convention of compiled puzzle programs: the caller stores the
+/// return address at `[rb+0]` right before jumping to the callee, which
+/// moves the relative base around its frame and jumps back through `[rb+0]`.
+fn return_address(program: &[isize], jump: usize) -> Option<usize> {
+    let store = jump.checked_sub(4)?;
+    let (opcode, operands) = decode_at(program, store)?;
+    let destination = operands.last()?;
+    if destination.mode != Mode::Relative || destination.value != 0 {
+        return None;
+    }
+    let value = constant_write(opcode, &operands)?;
+    if value >= 0 && (value as usize) < program.len() {
+        Some(value as usize)
+    } else {
+        None
+    }
+}
+
+fn terminator(
+    program: &[isize],
+    address: usize,
+    opcode: Opcode,
+    operands: &[Operand],
+) -> Option<Terminator> {
+    let (condition, target) = match opcode {
+        Opcode::Hlt => return Some(Terminator::Halt),
+        Opcode::Jnz | Opcode::Jz => (operands[0], operands[1]),
+        _ => return None,
+    };
+
+    let always = match condition.mode {
+        Mode::Immediate if (condition.value != 0) == (opcode == Opcode::Jnz) => true,
+        Mode::Immediate => return None,
+        _ => false,
+    };
+    let target = match target.mode {
+        Mode::Immediate if target.value >= 0 => Some(target.value as usize),
+        Mode::Relative if always && target.value == 0 => return Some(Terminator::Return),
+        _ => None,
+    };
+
+    if !always {
+        return Some(Terminator::Branch { target });
+    }
+    Some(match return_address(program, address) {
+        Some(return_to) => Terminator::Call { target, return_to },
+        None => Terminator::Jump { target },
+    })
+}
+
+/// Builds the control-flow graph of `program` without running it. Jumps whose
+/// targets are computed at runtime end the walk, so code only reached through
+/// them is reported as data.
+pub fn analyze(program: &[isize]) -> Cfg {
+    let mut instructions = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut functions = BTreeSet::new();
+    let mut invalid = BTreeSet::new();
+
+    let mut pending = vec![0];
+    leaders.insert(0);
+    functions.insert(0);
+
+    while let Some(mut address) = pending.pop() {
+        while !instructions.contains_key(&address) {
+            let (opcode, operands) = match decode_at(program, address) {
+                Some(decoded) => decoded,
+                None => {
+                    invalid.insert(address);
+                    break;
+                }
+            };
+            let next = address + operands.len() + 1;
+            let terminator = terminator(program, address, opcode, &operands);
+            instructions.insert(address, (opcode, operands, terminator));
+
+            let mut targets = Vec::new();
+            match terminator {
+                None => {
+                    address = next;
+                    continue;
+                }
+                Some(Terminator::Branch { target }) => {
+                    targets.extend(target);
+                    targets.push(next);
+                }
+                Some(Terminator::Jump { target }) => targets.extend(target),
+                Some(Terminator::Call { target, return_to }) => {
+                    functions.extend(target);
+                    targets.extend(target);
+                    targets.push(return_to);
+                }
+                Some(_) => (),
+            }
+            leaders.insert(next);
+            leaders.extend(targets.iter().copied());
+            pending.extend(targets);
+            break;
+        }
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut current: Option<BasicBlock> = None;
+    for (&address, (opcode, operands, terminator)) in &instructions {
+        let line = Line::Instruction {
+            address,
+            opcode: *opcode,
+            operands: operands.clone(),
+        };
+        let end = address + line.size();
+
+        let mut block = match current.take() {
+            Some(block) if block.end == address && !leaders.contains(&address) => block,
+            previous => {
+                if let Some(previous) = previous {
+                    blocks.insert(previous.start, previous);
+                }
+                BasicBlock {
+                    start: address,
+                    end: address,
+                    lines: Vec::new(),
+                    terminator: Terminator::Fallthrough,
+                }
+            }
+        };
+        block.lines.push(line);
+        block.end = end;
+
+        match terminator {
+            Some(terminator) => {
+                block.terminator = *terminator;
+                blocks.insert(block.start, block);
+            }
+            None if invalid.contains(&end) => {
+                block.terminator = Terminator::Invalid;
+                blocks.insert(block.start, block);
+            }
+            None => current = Some(block),
+        }
+    }
+    if let Some(block) = current {
+        blocks.insert(block.start, block);
+    }
+
+    Cfg {
+        blocks,
+        functions,
+        invalid,
+        size: program.len(),
+    }
+}
+
+impl Cfg {
+    /// The block containing `address`.
+    pub fn block_at(&self, address: usize) -> Option<&BasicBlock> {
+        self.blocks
+            .range(..=address)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| address < block.end)
+    }
+
+    pub fn is_reachable(&self, address: usize) -> bool {
+        self.block_at(address).is_some()
+    }
+
+    /// Address ranges covered by reachable code, adjacent blocks merged.
+    pub fn code_regions(&self) -> Vec<Range<usize>> {
+        let mut regions: Vec<Range<usize>> = Vec::new();
+        for block in self.blocks.values() {
+            match regions.last_mut() {
+                Some(region) if region.end == block.start => region.end = block.end,
+                _ => regions.push(block.start..block.end),
+            }
+        }
+        regions
+    }
+
+    /// Everything in the program that is not reachable code.
+    pub fn data_regions(&self) -> Vec<Range<usize>> {
+        let mut regions = Vec::new();
+        let mut start = 0;
+        for code in self.code_regions() {
+            if start < code.start {
+                regions.push(start..code.start);
+            }
+            start = code.end;
+        }
+        if start < self.size {
+            regions.push(start..self.size);
+        }
+        regions
+    }
+
+    /// Renders the graph in Graphviz DOT format. Function entries are drawn
+    /// with a double border, calls dashed and call returns dotted.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph cfg {{").unwrap();
+        writeln!(dot, "    node [shape=box, fontname=monospace];").unwrap();
+
+        for block in self.blocks.values() {
+            let label: String = block
+                .lines
+                .iter()
+                .map(|line| format!("{}\\l", line))
+                .collect();
+            let peripheries = if self.functions.contains(&block.start) {
+                2
+            } else {
+                1
+            };
+            writeln!(
+                dot,
+                "    b{} [label=\"{}\", peripheries={}];",
+                block.start, label, peripheries
+            )
+            .unwrap();
+        }
+        for address in &self.invalid {
+            writeln!(
+                dot,
+                "    b{} [label=\"{:04}: invalid\", color=red];",
+                address, address
+            )
+            .unwrap();
+        }
+
+        for block in self.blocks.values() {
+            for (target, kind) in block.successors() {
+                let style = match kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Taken => " [color=green]",
+                    EdgeKind::Jump => " [color=blue]",
+                    EdgeKind::Call => " [style=dashed]",
+                    EdgeKind::AfterCall => " [style=dotted]",
+                };
+                writeln!(dot, "    b{} -> b{}{};", block.start, target, style).unwrap();
+            }
+        }
+
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
@@ -0,0 +1,67 @@
+use crate::Intcode;
+
+fn is_ascii(value: isize) -> bool {
+    (0..128).contains(&value)
+}
+
+/// Output of an ASCII program: its text and the values following it that are
+/// not characters, usually the puzzle answer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AsciiOutput {
+    pub text: String,
+    pub values: Vec<isize>,
+}
+
+/// Splits `values` at the first non-ASCII value.
+pub fn split_ascii(values: &[isize]) -> AsciiOutput {
+    let end = values
+        .iter()
+        .position(|value| !is_ascii(*value))
+        .unwrap_or(values.len());
+    AsciiOutput {
+        text: values[..end]
+            .iter()
+            .map(|value| *value as u8 as char)
+            .collect(),
+        values: values[end..].to_vec(),
+    }
+}
+
+impl Intcode {
+    pub fn send_text(&mut self, text: &str) {
+        text.bytes().for_each(|byte| self.add_input(byte as isize));
+    }
+
+    /// Queues `line` followed by a newline.
+    pub fn send_line(&mut self, line: &str) {
+        self.send_text(line);
+        self.add_input('\n' as isize);
+    }
+
+    /// Takes the first complete line of output, without its newline.
+    pub fn read_line(&mut self) -> Option<String> {
+        let line = self.read_until("\n")?;
+        Some(line.trim_end_matches('\n').to_string())
+    }
+
+    /// Takes the output up to and including the first occurrence of `prompt`,
+    /// or nothing if the prompt was not printed yet. Stops at a non-ASCII
+    /// value.
+    pub fn read_until(&mut self, prompt: &str) -> Option<String> {
+        let text: String = self
+            .get_output()
+            .iter()
+            .take_while(|value| is_ascii(**value))
+            .map(|value| *value as u8 as char)
+            .collect();
+        let end = text.find(prompt)? + prompt.len();
+        self.output.drain(..end);
+        Some(text[..end].to_string())
+    }
+
+    /// Drains the output queue, separating trailing non-ASCII values.
+    pub fn take_ascii_output(&mut self) -> AsciiOutput {
+        let values: Vec<_> = self.output.drain(..).collect();
+        split_ascii(&values)
+    }
+}
@@ -0,0 +1,363 @@
+use crate::instruction::{Mode, Opcode};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssembleErrorKind {
+    UnknownMnemonic(String),
+    InvalidOperand(String),
+    InvalidLabel(String),
+    WrongOperandCount { expected: usize, found: usize },
+    WriteInImmediateMode,
+    DuplicateLabel(String),
+    UndefinedLabel(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub kind: AssembleErrorKind,
+}
+
+impl fmt::Display for AssembleErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssembleErrorKind::UnknownMnemonic(mnemonic) => {
+                write!(f, "unknown mnemonic `{}`", mnemonic)
+            }
+            AssembleErrorKind::InvalidOperand(operand) => {
+                write!(f, "invalid operand `{}`", operand)
+            }
+            AssembleErrorKind::InvalidLabel(label) => write!(f, "invalid label `{}`", label),
+            AssembleErrorKind::WrongOperandCount { expected, found } => {
+                write!(f, "expected {} operands, found {}", expected, found)
+            }
+            AssembleErrorKind::WriteInImmediateMode => write!(f, "write in immediate mode"),
+            AssembleErrorKind::DuplicateLabel(label) => write!(f, "duplicate label `{}`", label),
+            AssembleErrorKind::UndefinedLabel(label) => write!(f, "undefined label `{}`", label),
+        }
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl Error for AssembleError {}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Number(isize),
+    Label(String, isize),
+}
+
+#[derive(Clone, Debug)]
+struct Operand {
+    mode: Mode,
+    value: Value,
+}
+
+enum Statement {
+    Instruction(Opcode, Vec<Operand>),
+    Data(Vec<Value>),
+}
+
+struct Item {
+    line: usize,
+    statement: Statement,
+}
+
+/// Assembles mnemonic source into a program `Intcode::new` can load.
+///
+/// Each line holds an optional `label:`, then an instruction or a `db`
+/// directive, and an optional `;` comment. Operands are written the way the
+/// disassembler prints them: `[address]` for position mode, `#value` for
+/// immediate mode and `[rb+offset]` for relative mode. The written operand
+/// may be separated by `->` instead of a comma. Labels can stand in for any
+/// number but a relative offset, so `[rbuf]` is the address of `rbuf`.
+///
+/// ```text
+/// loop:   in -> [value]
+///         jz [value], #end
+///         out [value]
+///         jnz #1, #loop
+/// end:    hlt
+/// value:  db 0
+/// ```
+pub fn assemble(source: &str) -> Result<Vec<isize>, AssembleError> {
+    let mut labels = HashMap::new();
+    let mut items = Vec::new();
+    let mut address = 0;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let error = |kind| AssembleError { line, kind };
+
+        let mut text = text.split(';').next().unwrap().trim();
+        if let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if !is_identifier(label) {
+                return Err(error(AssembleErrorKind::InvalidLabel(label.to_string())));
+            }
+            if labels.insert(label.to_string(), address as isize).is_some() {
+                return Err(error(AssembleErrorKind::DuplicateLabel(label.to_string())));
+            }
+            text = text[colon + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(text).map_err(error)?;
+        address += match &statement {
+            Statement::Instruction(_, operands) => operands.len() + 1,
+            Statement::Data(values) => values.len(),
+        };
+        items.push(Item { line, statement });
+    }
+
+    let mut program = Vec::with_capacity(address);
+    for item in items {
+        let resolve = |value: &Value| match value {
+            Value::Number(number) => Ok(*number),
+            Value::Label(label, sign) => labels
+                .get(label)
+                .map(|address| sign * address)
+                .ok_or_else(|| AssembleError {
+                    line: item.line,
+                    kind: AssembleErrorKind::UndefinedLabel(label.clone()),
+                }),
+        };
+
+        match &item.statement {
+            Statement::Instruction(opcode, operands) => {
+                let modes = operands
+                    .iter()
+                    .enumerate()
+                    .map(|(i, operand)| operand.mode.code() * 10_isize.pow(i as u32 + 2))
+                    .sum::<isize>();
+                program.push(opcode.code() + modes);
+                for operand in operands {
+                    program.push(resolve(&operand.value)?);
+                }
+            }
+            Statement::Data(values) => {
+                for value in values {
+                    program.push(resolve(value)?);
+                }
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+fn parse_statement(text: &str) -> Result<Statement, AssembleErrorKind> {
+    let (mnemonic, rest) = match text.find(char::is_whitespace) {
+        Some(space) => (&text[..space], text[space..].trim()),
+        None => (text, ""),
+    };
+    let mnemonic = mnemonic.to_lowercase();
+    let (inputs, output) = match rest.find("->") {
+        Some(arrow) => (rest[..arrow].trim(), Some(rest[arrow + 2..].trim())),
+        None => (rest, None),
+    };
+    let mut arguments: Vec<_> = if inputs.is_empty() {
+        Vec::new()
+    } else {
+        inputs.split(',').map(str::trim).collect()
+    };
+    arguments.extend(output);
+
+    if mnemonic == "db" {
+        let values = arguments
+            .iter()
+            .map(|argument| {
+                parse_value(argument)
+                    .ok_or_else(|| AssembleErrorKind::InvalidOperand(argument.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        return Ok(Statement::Data(values));
+    }
+
+    let opcode = Opcode::ALL
+        .iter()
+        .find(|opcode| opcode.mnemonic() == mnemonic)
+        .copied()
+        .ok_or(AssembleErrorKind::UnknownMnemonic(mnemonic))?;
+
+    if arguments.len() != opcode.parameters() {
+        return Err(AssembleErrorKind::WrongOperandCount {
+            expected: opcode.parameters(),
+            found: arguments.len(),
+        });
+    }
+
+    let operands: Vec<_> = arguments
+        .iter()
+        .map(|argument| {
+            parse_operand(argument)
+                .ok_or_else(|| AssembleErrorKind::InvalidOperand(argument.to_string()))
+        })
+        .collect::<Result<_, _>>()?;
+
+    if opcode.writes() && operands.last().unwrap().mode == Mode::Immediate {
+        return Err(AssembleErrorKind::WriteInImmediateMode);
+    }
+
+    Ok(Statement::Instruction(opcode, operands))
+}
+
+fn parse_operand(text: &str) -> Option<Operand> {
+    if let Some(value) = text.strip_prefix('#') {
+        return Some(Operand {
+            mode: Mode::Immediate,
+            value: parse_value(value.trim())?,
+        });
+    }
+
+    let inner = text.strip_prefix('[')?.strip_suffix(']')?.trim();
+    if let Some(offset) = inner.strip_prefix("rb").and_then(parse_offset) {
+        return Some(Operand {
+            mode: Mode::Relative,
+            value: Value::Number(offset),
+        });
+    }
+
+    Some(Operand {
+        mode: Mode::Position,
+        value: parse_value(inner)?,
+    })
+}
+
+/// Parses what follows `rb` in a relative operand: nothing, or a sign and a
+/// number. Anything else means the operand is a label that starts with `rb`.
+fn parse_offset(text: &str) -> Option<isize> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(0);
+    }
+
+    let (sign, digits) = match text.strip_prefix('+') {
+        Some(digits) => ("", digits.trim()),
+        None => ("-", text.strip_prefix('-')?.trim()),
+    };
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    format!("{}{}", sign, digits).parse().ok()
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    if let Ok(number) = text.parse::<isize>() {
+        return Some(Value::Number(number));
+    }
+
+    let (sign, label) = match text.strip_prefix('-') {
+        Some(label) => (-1, label.trim()),
+        None => (1, text),
+    };
+    if is_identifier(label) {
+        Some(Value::Label(label.to_string(), sign))
+    } else {
+        None
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::{self, Line};
+    use crate::Program;
+
+    /// Turns disassembled lines back into source the assembler accepts.
+    fn source(program: &[isize]) -> String {
+        disassembler::disassemble(program)
+            .iter()
+            .map(|line| {
+                let text = line.to_string();
+                let text = &text[text.find(": ").unwrap() + 2..];
+                match line {
+                    Line::Instruction { .. } => format!("{}\n", text),
+                    Line::Data { .. } => format!("db {}\n", &text["DATA ".len()..]),
+                }
+            })
+            .collect()
+    }
+
+    fn round_trip(program: &[isize]) {
+        assert_eq!(assemble(&source(program)).unwrap(), program);
+    }
+
+    #[test]
+    fn assembles_the_documented_example() {
+        let program = assemble(
+            "loop:   in -> [value]
+                     jz [value], #end
+                     out [value]
+                     jnz #1, #loop
+             end:    hlt
+             value:  db 0",
+        )
+        .unwrap();
+
+        assert_eq!(program, [3, 11, 1006, 11, 10, 4, 11, 1105, 1, 0, 99, 0]);
+        round_trip(&program);
+    }
+
+    #[test]
+    fn relative_offsets_allow_whitespace() {
+        let program = assemble("add [rb], [rb + 5] -> [rb- 3]").unwrap();
+
+        assert_eq!(program, [22201, 0, 5, -3]);
+        round_trip(&program);
+    }
+
+    #[test]
+    fn labels_may_start_with_rb() {
+        let program = assemble(
+            "in -> [rbuf]
+             arb [rb_size]
+             hlt
+             rbuf: db 0
+             rb_size: db 4",
+        )
+        .unwrap();
+
+        assert_eq!(program, [3, 5, 9, 6, 99, 0, 4]);
+        round_trip(&program);
+    }
+
+    #[test]
+    fn relative_offsets_are_numbers() {
+        let error = assemble("x: arb [rb+x]").unwrap_err();
+
+        assert_eq!(
+            error.kind,
+            AssembleErrorKind::InvalidOperand("[rb+x]".to_string())
+        );
+    }
+
+    #[test]
+    fn smallest_relative_offset_survives_a_round_trip() {
+        round_trip(&[204, isize::MIN, 99]);
+    }
+
+    #[test]
+    fn boost_survives_a_round_trip() {
+        round_trip(&Program::load("../day09/input").unwrap());
+    }
+}
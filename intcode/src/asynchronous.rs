@@ -0,0 +1,155 @@
+use crate::io::IntcodeIo;
+use crate::{Intcode, IntcodeError, StepOutcome};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Instructions executed between two voluntary yields, so one busy machine
+/// cannot starve the others on a single-threaded executor.
+const YIELD_INTERVAL: usize = 1024;
+
+#[derive(Debug)]
+pub enum AsyncRunError<E> {
+    Intcode(IntcodeError),
+    Sink(E),
+}
+
+impl<E: fmt::Display> fmt::Display for AsyncRunError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsyncRunError::Intcode(error) => write!(f, "{}", error),
+            AsyncRunError::Sink(error) => write!(f, "output sink failed: {}", error),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for AsyncRunError<E> {}
+
+#[derive(Default)]
+struct PendingIo {
+    /// Values queued on the machine before it was run, read first.
+    queued: VecDeque<isize>,
+    input: Option<isize>,
+    output: Vec<isize>,
+}
+
+impl IntcodeIo for PendingIo {
+    fn read(&mut self) -> Option<isize> {
+        self.queued.pop_front().or_else(|| self.input.take())
+    }
+
+    fn write(&mut self, value: isize) {
+        self.output.push(value);
+    }
+}
+
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/// Returns control to the executor once, letting other tasks run.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// Async front-end over `Intcode`: `in` awaits the next item of a stream and
+/// `out` sends to a sink.
+pub struct AsyncIntcode {
+    intcode: Intcode,
+}
+
+impl AsyncIntcode {
+    pub fn new(intcode: Intcode) -> Self {
+        Self { intcode }
+    }
+
+    pub fn intcode(&self) -> &Intcode {
+        &self.intcode
+    }
+
+    pub fn into_inner(self) -> Intcode {
+        self.intcode
+    }
+
+    /// Runs until the program halts or a watchpoint fires. Values queued with
+    /// `Intcode::add_input` are read before `input`, and those left over stay
+    /// queued. If `input` ends while the program waits for a value, returns
+    /// `StepOutcome::AwaitsInput` and the machine can be resumed with another
+    /// stream.
+    pub async fn run<I, O>(
+        &mut self,
+        input: I,
+        output: O,
+    ) -> Result<StepOutcome, AsyncRunError<O::Error>>
+    where
+        I: Stream<Item = isize> + Unpin,
+        O: Sink<isize> + Unpin,
+    {
+        let mut io = PendingIo {
+            queued: mem::take(&mut self.intcode.input),
+            ..PendingIo::default()
+        };
+        let result = self.drive(&mut io, input, output).await;
+        self.intcode.input = io.queued;
+        result
+    }
+
+    async fn drive<I, O>(
+        &mut self,
+        io: &mut PendingIo,
+        mut input: I,
+        mut output: O,
+    ) -> Result<StepOutcome, AsyncRunError<O::Error>>
+    where
+        I: Stream<Item = isize> + Unpin,
+        O: Sink<isize> + Unpin,
+    {
+        let mut executed = 0;
+
+        loop {
+            let outcome = self
+                .intcode
+                .step_with_io(io)
+                .map_err(AsyncRunError::Intcode)?;
+
+            for value in io.output.drain(..) {
+                output.send(value).await.map_err(AsyncRunError::Sink)?;
+            }
+
+            match outcome {
+                StepOutcome::Executed => {
+                    executed += 1;
+                    if executed % YIELD_INTERVAL == 0 {
+                        yield_now().await;
+                    }
+                }
+                StepOutcome::AwaitsInput => match input.next().await {
+                    Some(value) => {
+                        io.input = Some(value);
+                        yield_now().await;
+                    }
+                    None => return Ok(StepOutcome::AwaitsInput),
+                },
+                outcome => return Ok(outcome),
+            }
+        }
+    }
+}
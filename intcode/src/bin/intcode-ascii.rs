@@ -0,0 +1,41 @@
+use intcode::{Intcode, Program};
+use std::io::{self, BufRead, Write};
+use std::{env, process};
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| String::from("input"));
+    let program = Program::load(&path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    });
+
+    let mut intcode = Intcode::new(&program);
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    loop {
+        if let Err(error) = intcode.run() {
+            eprintln!("error: {}", error);
+            break;
+        }
+
+        while let Some(value) = intcode.get_first_output() {
+            if (0..128).contains(&value) {
+                write!(stdout, "{}", value as u8 as char).unwrap();
+            } else {
+                writeln!(stdout, "{}", value).unwrap();
+            }
+        }
+        stdout.flush().unwrap();
+
+        if intcode.finished() {
+            break;
+        }
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        intcode.send_line(line.trim_end_matches(&['\r', '\n'][..]));
+    }
+}
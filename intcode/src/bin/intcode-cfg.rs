@@ -0,0 +1,17 @@
+use intcode::{analysis, Program};
+use std::{env, process};
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| String::from("input"));
+    let program = Program::load(&path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    });
+
+    let cfg = analysis::analyze(&program);
+    print!("{}", cfg.to_dot());
+
+    for region in cfg.data_regions() {
+        eprintln!("data: {}..{}", region.start, region.end);
+    }
+}
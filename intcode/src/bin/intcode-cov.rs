@@ -0,0 +1,54 @@
+use intcode::{Intcode, Program};
+use std::{env, process};
+
+const USAGE: &str = "usage: intcode-cov [--lcov] <program> [input]...";
+
+fn main() {
+    let mut arguments: Vec<_> = env::args().skip(1).collect();
+    let lcov = match arguments.iter().position(|argument| argument == "--lcov") {
+        Some(index) => {
+            arguments.remove(index);
+            true
+        }
+        None => false,
+    };
+    if arguments.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let program = Program::load(&arguments[0]).unwrap_or_else(|error| {
+        eprintln!("{}: {}", arguments[0], error);
+        process::exit(1);
+    });
+
+    let mut intcode = Intcode::new(&program);
+    for input in &arguments[1..] {
+        intcode.add_input(input.parse().expect("invalid input"));
+    }
+
+    intcode.start_coverage();
+    let outcome = intcode.run();
+    let coverage = intcode.take_coverage().unwrap();
+
+    if lcov {
+        print!("{}", coverage.lcov(&program, &arguments[0]));
+        return;
+    }
+
+    print!("{}", coverage.annotate(&program));
+    let summary = coverage.summary(&program);
+    println!();
+    println!("outcome:      {:?}", outcome);
+    println!(
+        "instructions: {}/{} executed",
+        summary.instructions_hit, summary.instructions_found
+    );
+    println!(
+        "branches:     {}/{} taken",
+        summary.branches_hit, summary.branches_found
+    );
+    for region in coverage.uncovered(&program) {
+        println!("never executed: {}..{}", region.start, region.end);
+    }
+}
@@ -0,0 +1,357 @@
+use intcode::disassembler::{self, Line};
+use intcode::self_modifying::OnCodeWrite;
+use intcode::{Intcode, MemoryAccess, Program, StepOutcome, WatchKind};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::{env, process};
+
+const DEFAULT_HISTORY: usize = 1_000_000;
+
+const HELP: &str = "\
+commands:
+  s, step [n]           execute n instructions (default 1)
+  c, continue           run until a breakpoint, halt, input request or error
+  rs, reverse-step [n]  undo n instructions (default 1)
+  rc, reverse-continue  undo instructions until a breakpoint or the history ends
+  lastwrite <addr>      undo instructions until the last write to addr
+  history [n]           keep an undo log of n instructions for the reverse
+                        commands (default 1000000, 0 disables it, off at start)
+  b, break <pc>         set a breakpoint
+  d, delete <pc>        remove a breakpoint
+  bl                    list breakpoints
+  watch <addr> [end]    stop when addr (or addr..end) is written
+  rwatch <addr> [end]   stop when addr (or addr..end) is read
+  awatch <addr> [end]   stop when addr (or addr..end) is read or written
+  unwatch <addr> [end]  remove watchpoints on addr (or addr..end)
+  wl                    list watchpoints
+  smc <0|1>             1 stops when code that already ran is overwritten,
+                        0 turns the check off
+  l, list [addr] [n]    disassemble n instructions starting at addr (default pc)
+  x <addr> [n]          dump n memory words starting at addr
+  poke <addr> <value>   write value to memory
+  i, input <value>...   queue input values
+  o, output             print and drain the output queue
+  r, regs               show pc, relative base, state and queues
+  h, help               show this help
+  q, quit               exit";
+
+struct Debugger {
+    intcode: Intcode,
+    breakpoints: BTreeSet<usize>,
+}
+
+impl Debugger {
+    fn new(program: &[isize]) -> Self {
+        Self {
+            intcode: Intcode::new(program),
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    fn decode_at(&self, address: usize) -> Line {
+        let window: Vec<_> = (address..address + 4)
+            .map(|a| self.intcode.read_from_memory(a))
+            .collect();
+
+        match disassembler::decode_at(&window, 0) {
+            Some((opcode, operands)) => Line::Instruction {
+                address,
+                opcode,
+                operands,
+            },
+            None => Line::Data {
+                address,
+                values: vec![window[0]],
+            },
+        }
+    }
+
+    fn step(&mut self) -> bool {
+        match self.intcode.execute_single_instruction() {
+            Ok(StepOutcome::Executed) => true,
+            Ok(StepOutcome::AwaitsInput) => {
+                println!("awaiting input");
+                false
+            }
+            Ok(StepOutcome::Finished) => {
+                println!("program finished");
+                false
+            }
+            Ok(StepOutcome::Watchpoint(_)) => {
+                self.print_watch_hits();
+                false
+            }
+            Ok(StepOutcome::CodeWrite(write)) => {
+                println!("code write: {}", write);
+                self.print_watch_hits();
+                false
+            }
+            Ok(outcome) => {
+                println!("stopped: {:?}", outcome);
+                false
+            }
+            Err(error) => {
+                println!("error: {}", error);
+                false
+            }
+        }
+    }
+
+    fn print_watch_hits(&self) {
+        for hit in self.intcode.watch_hits() {
+            match hit.access {
+                MemoryAccess::Read { value } => println!(
+                    "watchpoint: {:04} read {} from [{}]",
+                    hit.pc, value, hit.address
+                ),
+                MemoryAccess::Write {
+                    old_value,
+                    new_value,
+                } => println!(
+                    "watchpoint: {:04} wrote {} to [{}] (was {})",
+                    hit.pc, new_value, hit.address, old_value
+                ),
+            }
+        }
+    }
+
+    fn step_n(&mut self, count: usize) {
+        for _ in 0..count {
+            if !self.step() {
+                break;
+            }
+        }
+        self.print_current();
+    }
+
+    fn resume(&mut self) {
+        while self.step() {
+            if self.breakpoints.contains(&self.intcode.pc()) {
+                println!("breakpoint at {:04}", self.intcode.pc());
+                break;
+            }
+        }
+        self.print_current();
+    }
+
+    fn step_back_n(&mut self, count: usize) {
+        for _ in 0..count {
+            if !self.intcode.step_back() {
+                println!("no more history");
+                break;
+            }
+        }
+        self.print_current();
+    }
+
+    fn reverse(&mut self) {
+        while self.intcode.step_back() {
+            if self.breakpoints.contains(&self.intcode.pc()) {
+                println!("breakpoint at {:04}", self.intcode.pc());
+                break;
+            }
+        }
+        self.print_current();
+    }
+
+    fn last_write(&mut self, address: usize) {
+        match self.intcode.run_back_to_write(address) {
+            Some(pc) => println!("[{}] last written by {:04}", address, pc),
+            None => println!("no recorded write to [{}]", address),
+        }
+        self.print_current();
+    }
+
+    fn print_current(&self) {
+        let line = self.decode_at(self.intcode.pc());
+        println!("=> {}", line);
+    }
+
+    fn list(&self, address: usize, count: usize) {
+        let mut address = address;
+        for _ in 0..count {
+            let line = self.decode_at(address);
+            let marker = if address == self.intcode.pc() {
+                "=>"
+            } else if self.breakpoints.contains(&address) {
+                " *"
+            } else {
+                "  "
+            };
+            println!("{} {}", marker, line);
+            address += line.size();
+        }
+    }
+
+    fn dump(&self, address: usize, count: usize) {
+        for row in (address..address + count).step_by(8) {
+            let values: Vec<_> = (row..(row + 8).min(address + count))
+                .map(|a| format!("{:>8}", self.intcode.read_from_memory(a)))
+                .collect();
+            println!("{:04}: {}", row, values.join(" "));
+        }
+    }
+
+    fn print_registers(&self) {
+        println!("pc:            {:04}", self.intcode.pc());
+        println!("relative base: {}", self.intcode.relative_base());
+        println!(
+            "state:         {}",
+            if self.intcode.finished() {
+                "finished"
+            } else if self.intcode.awaits_input() {
+                "awaiting input"
+            } else {
+                "running"
+            }
+        );
+        println!("input:         {:?}", self.intcode.get_input());
+        println!("output:        {:?}", self.intcode.get_output());
+    }
+
+    fn execute(&mut self, command: &str, arguments: &[isize]) -> Result<bool, String> {
+        let argument = |index: usize| {
+            arguments
+                .get(index)
+                .copied()
+                .ok_or_else(|| format!("missing argument {}", index + 1))
+        };
+        let address = |index: usize| {
+            argument(index).and_then(|value| {
+                if value < 0 {
+                    Err(format!("negative address {}", value))
+                } else {
+                    Ok(value as usize)
+                }
+            })
+        };
+        let count = |index: usize, default: usize| match arguments.get(index) {
+            Some(&value) if value < 0 => Err(format!("negative count {}", value)),
+            Some(&value) => Ok(value as usize),
+            None => Ok(default),
+        };
+        let optional_address = |index: usize, default: usize| {
+            if index < arguments.len() {
+                address(index)
+            } else {
+                Ok(default)
+            }
+        };
+
+        match command {
+            "rs" | "reverse-step" | "rc" | "reverse-continue" | "lastwrite"
+                if !self.intcode.history_enabled() =>
+            {
+                return Err(String::from(
+                    "history is disabled, enable it with `history <n>`",
+                ));
+            }
+            "s" | "step" => self.step_n(count(0, 1)?),
+            "c" | "continue" => self.resume(),
+            "rs" | "reverse-step" => self.step_back_n(count(0, 1)?),
+            "rc" | "reverse-continue" => self.reverse(),
+            "lastwrite" => self.last_write(address(0)?),
+            "history" => match count(0, DEFAULT_HISTORY)? {
+                0 => self.intcode.disable_history(),
+                capacity => self.intcode.enable_history(capacity),
+            },
+            "b" | "break" => {
+                self.breakpoints.insert(address(0)?);
+            }
+            "d" | "delete" => {
+                self.breakpoints.remove(&address(0)?);
+            }
+            "bl" => self
+                .breakpoints
+                .iter()
+                .for_each(|breakpoint| println!("{:04}", breakpoint)),
+            "watch" | "rwatch" | "awatch" => {
+                let kind = match command {
+                    "watch" => WatchKind::Write,
+                    "rwatch" => WatchKind::Read,
+                    _ => WatchKind::Access,
+                };
+                let start = address(0)?;
+                let end = optional_address(1, start + 1)?;
+                self.intcode.add_watchpoint(start..end, kind);
+            }
+            "unwatch" => {
+                let start = address(0)?;
+                let end = optional_address(1, start + 1)?;
+                for kind in [WatchKind::Read, WatchKind::Write, WatchKind::Access].iter() {
+                    self.intcode.remove_watchpoint(start..end, *kind);
+                }
+            }
+            "wl" => self
+                .intcode
+                .watchpoints()
+                .iter()
+                .for_each(|watchpoint| println!("{:?} {:?}", watchpoint.range, watchpoint.kind)),
+            "smc" => match argument(0)? {
+                0 => self.intcode.disable_self_modification_checks(),
+                1 => self
+                    .intcode
+                    .enable_self_modification_checks(OnCodeWrite::Halt),
+                value => return Err(format!("expected 0 or 1, found {}", value)),
+            },
+            "l" | "list" => {
+                let start = optional_address(0, self.intcode.pc())?;
+                self.list(start, count(1, 10)?);
+            }
+            "x" => self.dump(address(0)?, count(1, 8)?),
+            "poke" => self.intcode.write_to_memory(address(0)?, argument(1)?),
+            "i" | "input" => arguments
+                .iter()
+                .for_each(|value| self.intcode.add_input(*value)),
+            "o" | "output" => {
+                while let Some(value) = self.intcode.get_first_output() {
+                    println!("{}", value);
+                }
+            }
+            "r" | "regs" => self.print_registers(),
+            "h" | "help" => println!("{}", HELP),
+            "q" | "quit" => return Ok(false),
+            _ => return Err(format!("unknown command `{}`", command)),
+        }
+
+        Ok(true)
+    }
+}
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| String::from("input"));
+    let program = Program::load(&path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    });
+
+    let mut debugger = Debugger::new(&program);
+    debugger.print_current();
+
+    let stdin = io::stdin();
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => continue,
+        };
+        let arguments: Result<Vec<_>, _> = words.map(|word| word.parse::<isize>()).collect();
+
+        match arguments {
+            Ok(arguments) => match debugger.execute(command, &arguments) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(message) => println!("{}", message),
+            },
+            Err(error) => println!("invalid argument: {}", error),
+        }
+    }
+}
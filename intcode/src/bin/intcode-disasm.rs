@@ -0,0 +1,12 @@
+use intcode::{disassembler, Program};
+use std::{env, process};
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| String::from("input"));
+    let program = Program::load(&path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    });
+
+    print!("{}", disassembler::listing(&program));
+}
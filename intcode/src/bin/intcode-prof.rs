@@ -0,0 +1,41 @@
+use intcode::{Intcode, Program};
+use std::{env, process};
+
+const USAGE: &str = "usage: intcode-prof [--folded] <program> [input]...";
+
+fn main() {
+    let mut arguments: Vec<_> = env::args().skip(1).collect();
+    let folded = match arguments.iter().position(|argument| argument == "--folded") {
+        Some(index) => {
+            arguments.remove(index);
+            true
+        }
+        None => false,
+    };
+    if arguments.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let program = Program::load(&arguments[0]).unwrap_or_else(|error| {
+        eprintln!("{}: {}", arguments[0], error);
+        process::exit(1);
+    });
+
+    let mut intcode = Intcode::new(&program);
+    for input in &arguments[1..] {
+        intcode.add_input(input.parse().expect("invalid input"));
+    }
+
+    intcode.start_profile();
+    let outcome = intcode.run();
+    let profile = intcode.take_profile().unwrap();
+
+    if folded {
+        print!("{}", profile.folded());
+    } else {
+        println!("outcome: {:?}", outcome);
+        println!("output:  {:?}", intcode.get_output());
+        print!("{}", profile);
+    }
+}
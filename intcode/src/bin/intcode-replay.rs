@@ -0,0 +1,85 @@
+use intcode::trace::{self, Trace};
+use intcode::{Intcode, Program};
+use std::fmt;
+use std::io::{self, Write};
+use std::{env, fs, process};
+
+const USAGE: &str = "\
+usage:
+  intcode-replay record <program> <trace> [input]...
+  intcode-replay check <program> <trace>
+  intcode-replay dump <trace>";
+
+fn fail(context: &str, error: impl fmt::Display) -> ! {
+    eprintln!("{}: {}", context, error);
+    process::exit(1);
+}
+
+fn read_program(path: &str) -> Program {
+    Program::load(path).unwrap_or_else(|error| fail(path, error))
+}
+
+fn read_trace(path: &str) -> Trace {
+    let bytes = fs::read(path).unwrap_or_else(|error| fail(path, error));
+    Trace::parse(&bytes).unwrap_or_else(|error| fail(path, error))
+}
+
+fn write_trace(trace: &Trace, path: &str) -> io::Result<()> {
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+    trace.write(&mut file)?;
+    file.flush()
+}
+
+fn record(program: &[isize], path: &str, inputs: &[String]) {
+    let mut intcode = Intcode::new(program);
+    for input in inputs {
+        let value = input
+            .parse()
+            .unwrap_or_else(|error| fail(&format!("invalid input `{}`", input), error));
+        intcode.add_input(value);
+    }
+
+    intcode.start_trace();
+    let outcome = intcode.run();
+    let trace = intcode.take_trace().unwrap();
+
+    write_trace(&trace, path).unwrap_or_else(|error| fail(path, error));
+
+    println!(
+        "recorded {} instructions, outcome {:?}",
+        trace.entries.len(),
+        outcome
+    );
+}
+
+fn check(program: &[isize], trace: &Trace) {
+    match trace::replay(program, trace) {
+        Ok(steps) => println!("replayed {} instructions without divergence", steps),
+        Err(divergence) => {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<_> = env::args().skip(1).collect();
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["record", program, path, ..] => record(&read_program(program), path, &args[3..]),
+        ["check", program, path] => check(&read_program(program), &read_trace(path)),
+        ["dump", path] => read_trace(path)
+            .entries
+            .iter()
+            .for_each(|entry| println!("{}", entry)),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
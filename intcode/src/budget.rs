@@ -0,0 +1,74 @@
+use crate::io::IntcodeIo;
+use crate::{Intcode, IntcodeError, StepOutcome};
+use std::time::{Duration, Instant};
+
+/// Instructions executed between two checks of the wall clock.
+const CLOCK_CHECK_INTERVAL: u64 = 1024;
+
+/// Limits for `Intcode::run_with_budget`. An empty budget runs like `run`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub instructions: Option<u64>,
+    pub deadline: Option<Instant>,
+}
+
+impl Budget {
+    pub fn instructions(instructions: u64) -> Self {
+        Self {
+            instructions: Some(instructions),
+            deadline: None,
+        }
+    }
+
+    pub fn timeout(timeout: Duration) -> Self {
+        Self {
+            instructions: None,
+            deadline: Some(Instant::now() + timeout),
+        }
+    }
+
+    pub fn with_deadline(self, deadline: Instant) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..self
+        }
+    }
+}
+
+impl Intcode {
+    /// Runs like `run`, but stops with `StepOutcome::BudgetExhausted` once the
+    /// budget is used up. The machine is left between two instructions and can
+    /// be resumed with another call.
+    pub fn run_with_budget(&mut self, budget: Budget) -> Result<StepOutcome, IntcodeError> {
+        if self.awaits_input {
+            return Ok(StepOutcome::AwaitsInput);
+        }
+        self.with_queues(|intcode, io| intcode.run_with_io_and_budget(io, budget))
+    }
+
+    /// `run_with_io` with a budget, see `run_with_budget`.
+    pub fn run_with_io_and_budget(
+        &mut self,
+        io: &mut dyn IntcodeIo,
+        budget: Budget,
+    ) -> Result<StepOutcome, IntcodeError> {
+        let mut executed = 0;
+        loop {
+            if budget.instructions.is_some_and(|limit| executed >= limit) {
+                return Ok(StepOutcome::BudgetExhausted);
+            }
+            if executed % CLOCK_CHECK_INTERVAL == 0
+                && budget
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Ok(StepOutcome::BudgetExhausted);
+            }
+
+            match self.step_with_io(io)? {
+                StepOutcome::Executed => executed += 1,
+                outcome => return Ok(outcome),
+            }
+        }
+    }
+}
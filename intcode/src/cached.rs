@@ -0,0 +1,383 @@
+//! Intcode with a cache of decoded instructions.
+//!
+//! `Intcode` decodes the instruction word and fetches its raw parameters on
+//! every step. `CachedIntcode` does that once per address and keeps the result
+//! until a write lands on one of the instruction's words, so self-modifying
+//! programs behave exactly as they do in the interpreter. It has none of the
+//! debugging hooks and only runs the built-in instruction set.
+
+use crate::instruction::{Mode, Opcode};
+use crate::io::{IntcodeIo, QueueIo};
+use crate::memory::Memory;
+use crate::semantics;
+use crate::{ErrorCause, IntcodeError, Snapshot, StepOutcome};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::mem;
+
+/// Instructions at or beyond this address are decoded on every visit.
+const CACHE_LIMIT: usize = 1 << 20;
+
+/// Longest instruction, so a write at `address` can only affect instructions
+/// starting in `address - (MAX_LENGTH - 1)..=address`.
+const MAX_LENGTH: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+struct Decoded {
+    opcode: Opcode,
+    modes: [Mode; 3],
+    parameters: [isize; 3],
+}
+
+#[derive(Clone, Debug)]
+pub struct CachedIntcode {
+    memory: Memory,
+    cache: Vec<Option<Decoded>>,
+    pc: usize,
+    relative_base: isize,
+    input: VecDeque<isize>,
+    output: VecDeque<isize>,
+    finished: bool,
+    awaits_input: bool,
+    checked: bool,
+    hits: u64,
+    misses: u64,
+}
+
+impl CachedIntcode {
+    pub fn new(program: &[isize]) -> Self {
+        Self {
+            memory: Memory::new(program),
+            cache: vec![None; program.len().min(CACHE_LIMIT)],
+            pc: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            finished: false,
+            awaits_input: false,
+            checked: false,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Continues from a state captured by `Intcode::snapshot`, with an empty
+    /// cache.
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        Self {
+            memory: snapshot.memory.clone(),
+            cache: Vec::new(),
+            pc: snapshot.pc,
+            relative_base: snapshot.relative_base,
+            input: snapshot.input.clone(),
+            output: snapshot.output.clone(),
+            finished: snapshot.finished,
+            awaits_input: snapshot.awaits_input,
+            checked: false,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// State that `Intcode::from_snapshot` can pick up, e.g. to debug a
+    /// machine that was run here for speed.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.clone(),
+            pc: self.pc,
+            relative_base: self.relative_base,
+            input: self.input.clone(),
+            output: self.output.clone(),
+            finished: self.finished,
+            awaits_input: self.awaits_input,
+        }
+    }
+
+    /// See `Intcode::enable_overflow_checks`.
+    pub fn enable_overflow_checks(&mut self) {
+        self.checked = true;
+    }
+
+    pub fn disable_overflow_checks(&mut self) {
+        self.checked = false;
+    }
+
+    pub fn run(&mut self) -> Result<StepOutcome, IntcodeError> {
+        if self.awaits_input {
+            return Ok(StepOutcome::AwaitsInput);
+        }
+        self.with_queues(|intcode, io| intcode.run_with_io(io))
+    }
+
+    pub fn execute_single_instruction(&mut self) -> Result<StepOutcome, IntcodeError> {
+        if self.awaits_input {
+            return Ok(StepOutcome::AwaitsInput);
+        }
+        self.with_queues(|intcode, io| intcode.step_with_io(io))
+    }
+
+    /// Runs until the program halts or `io` has no input ready. The internal
+    /// input and output queues are not used.
+    pub fn run_with_io(&mut self, io: &mut dyn IntcodeIo) -> Result<StepOutcome, IntcodeError> {
+        loop {
+            match self.step_with_io(io)? {
+                StepOutcome::Executed => continue,
+                outcome => return Ok(outcome),
+            }
+        }
+    }
+
+    pub fn step_with_io(&mut self, io: &mut dyn IntcodeIo) -> Result<StepOutcome, IntcodeError> {
+        if self.finished {
+            return Ok(StepOutcome::Finished);
+        }
+        self.awaits_input = false;
+
+        let pc = self.pc;
+        match self
+            .decoded(pc)
+            .and_then(|decoded| self.execute(decoded, io))
+        {
+            Ok(()) if self.finished => Ok(StepOutcome::Finished),
+            Ok(()) if self.awaits_input => Ok(StepOutcome::AwaitsInput),
+            Ok(()) => Ok(StepOutcome::Executed),
+            Err(cause) => {
+                self.pc = pc;
+                Err(IntcodeError::new(pc, self.memory.get(pc), cause))
+            }
+        }
+    }
+
+    fn with_queues<T>(&mut self, f: impl FnOnce(&mut Self, &mut QueueIo) -> T) -> T {
+        let mut io = QueueIo {
+            input: mem::take(&mut self.input),
+            output: mem::take(&mut self.output),
+        };
+        let result = f(self, &mut io);
+        self.input = io.input;
+        self.output = io.output;
+        result
+    }
+
+    /// The instruction at `pc`, from the cache if it has been decoded since the
+    /// last write to it.
+    fn decoded(&mut self, pc: usize) -> Result<Decoded, ErrorCause> {
+        if let Some(Some(decoded)) = self.cache.get(pc) {
+            self.hits += 1;
+            return Ok(*decoded);
+        }
+        self.misses += 1;
+
+        let (opcode, mode_1, mode_2, mode_3) = semantics::decode(&self.memory.get(pc))?;
+        let decoded = Decoded {
+            opcode: Opcode::try_from(opcode)?,
+            modes: [mode_1, mode_2, mode_3],
+            parameters: [
+                self.memory.get(pc + 1),
+                self.memory.get(pc + 2),
+                self.memory.get(pc + 3),
+            ],
+        };
+        if pc < CACHE_LIMIT {
+            if pc >= self.cache.len() {
+                self.cache.resize(pc + 1, None);
+            }
+            self.cache[pc] = Some(decoded);
+        }
+        Ok(decoded)
+    }
+
+    fn execute(&mut self, decoded: Decoded, io: &mut dyn IntcodeIo) -> Result<(), ErrorCause> {
+        let Decoded {
+            opcode,
+            modes,
+            parameters,
+        } = decoded;
+
+        match opcode {
+            Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => {
+                let a = self.read(modes[0], parameters[0])?;
+                let b = self.read(modes[1], parameters[1])?;
+                let result = semantics::evaluate(opcode, &a, &b, self.checked)?;
+                let address = self.write_address(modes[2], parameters[2])?;
+                self.pc += 4;
+                self.store(address, result);
+            }
+            Opcode::In => {
+                let address = self.write_address(modes[0], parameters[0])?;
+                match io.read() {
+                    Some(input) => {
+                        self.pc += 2;
+                        self.store(address, input);
+                    }
+                    None => self.awaits_input = true,
+                }
+            }
+            Opcode::Out => {
+                let output = self.read(modes[0], parameters[0])?;
+                self.pc += 2;
+                io.write(output);
+            }
+            Opcode::Jnz | Opcode::Jz => {
+                let condition = self.read(modes[0], parameters[0])?;
+                let target = self.read(modes[1], parameters[1])?;
+                self.pc = if semantics::jumps(opcode, &condition) {
+                    semantics::to_address(&target)?
+                } else {
+                    self.pc + 3
+                };
+            }
+            Opcode::Arb => {
+                let offset = self.read(modes[0], parameters[0])?;
+                self.relative_base =
+                    semantics::relative_address(self.relative_base, &offset, self.checked)?;
+                self.pc += 2;
+            }
+            Opcode::Hlt => {
+                self.pc += 1;
+                self.finished = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, mode: Mode, raw: isize) -> Result<isize, ErrorCause> {
+        match mode {
+            Mode::Immediate => Ok(raw),
+            _ => Ok(self.memory.get(semantics::address(
+                mode,
+                &raw,
+                self.relative_base,
+                self.checked,
+            )?)),
+        }
+    }
+
+    fn write_address(&self, mode: Mode, raw: isize) -> Result<usize, ErrorCause> {
+        semantics::address(mode, &raw, self.relative_base, self.checked)
+    }
+
+    /// Writes `value` and drops every cached instruction that covers
+    /// `address`.
+    fn store(&mut self, address: usize, value: isize) {
+        self.memory.set(address, value);
+        let first = address.saturating_sub(MAX_LENGTH - 1);
+        for entry in self.cache.iter_mut().take(address + 1).skip(first) {
+            *entry = None;
+        }
+    }
+
+    /// Number of steps that found their instruction in the cache and that had
+    /// to decode it.
+    pub fn cache_stats(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+
+    pub fn read_from_memory(&self, address: usize) -> isize {
+        self.memory.get(address)
+    }
+
+    pub fn write_to_memory(&mut self, address: usize, value: isize) {
+        self.store(address, value);
+    }
+
+    pub fn add_input(&mut self, value: isize) {
+        self.awaits_input = false;
+        self.input.push_back(value);
+    }
+
+    pub fn get_input(&self) -> &VecDeque<isize> {
+        &self.input
+    }
+
+    pub fn has_output(&self) -> bool {
+        !self.output.is_empty()
+    }
+
+    pub fn get_output(&self) -> &VecDeque<isize> {
+        &self.output
+    }
+
+    pub fn get_first_output(&mut self) -> Option<isize> {
+        self.output.pop_front()
+    }
+
+    pub fn get_last_output(&mut self) -> Option<isize> {
+        self.output.pop_back()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn awaits_input(&self) -> bool {
+        self.awaits_input
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> isize {
+        self.relative_base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Intcode, Program};
+
+    /// Runs both machines one instruction at a time and checks they agree on
+    /// every step. Returns the outputs.
+    fn run_side_by_side(program: &[isize], inputs: &[isize]) -> Vec<isize> {
+        let mut intcode = Intcode::new(program);
+        let mut cached = CachedIntcode::new(program);
+        for input in inputs {
+            intcode.add_input(*input);
+            cached.add_input(*input);
+        }
+
+        loop {
+            let expected = intcode.execute_single_instruction();
+            let actual = cached.execute_single_instruction();
+            let step = intcode.pc();
+
+            assert_eq!(actual, expected, "outcome after pc {}", step);
+            assert_eq!(cached.pc(), intcode.pc());
+            assert_eq!(cached.relative_base(), intcode.relative_base());
+            assert_eq!(cached.get_output(), intcode.get_output());
+            assert_eq!(
+                cached.snapshot().memory.non_zero(),
+                intcode.snapshot().memory.non_zero(),
+                "memory after pc {}",
+                step
+            );
+
+            if expected != Ok(StepOutcome::Executed) {
+                return intcode.get_output().iter().copied().collect();
+            }
+        }
+    }
+
+    #[test]
+    fn boost_matches_the_interpreter() {
+        let program = Program::load("../day09/input").unwrap();
+        assert_eq!(run_side_by_side(&program, &[1]), [4_288_078_517]);
+    }
+
+    #[test]
+    fn rewritten_instructions_match_the_interpreter() {
+        let program = [
+            1001, 5, 10, 5, // add [5], #10 -> [5], the operand of the next out
+            104, 1, // out #1
+            1001, 10, 1, 10, // add [10], #1 -> [10], the opcode of the next instruction
+            0, 25, 26, 27, // add, then mul [25], [26] -> [27]
+            4, 27, // out [27]
+            1001, 24, -1, 24, // add [24], #-1 -> [24]
+            1005, 24, 0, // jnz [24], #0
+            99, 2, 6, 7, 0,
+        ];
+        assert_eq!(run_side_by_side(&program, &[]), [11, 13, 21, 42]);
+    }
+}
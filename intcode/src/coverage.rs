@@ -0,0 +1,234 @@
+use crate::analysis::{self, Terminator};
+use crate::disassembler::{self, Line};
+use crate::instruction::Opcode;
+use crate::Intcode;
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BranchCount {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/// Executed addresses and branch outcomes, collected by
+/// `Intcode::start_coverage` and merged across runs with `merge`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    pub hits: HashMap<usize, u64>,
+    pub branches: HashMap<usize, BranchCount>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CoverageSummary {
+    pub instructions_found: usize,
+    pub instructions_hit: usize,
+    pub branches_found: usize,
+    pub branches_hit: usize,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn merge(&mut self, other: &Coverage) {
+        for (pc, count) in &other.hits {
+            *self.hits.entry(*pc).or_insert(0) += count;
+        }
+        for (pc, count) in &other.branches {
+            let branch = self.branches.entry(*pc).or_default();
+            branch.taken += count.taken;
+            branch.not_taken += count.not_taken;
+        }
+    }
+
+    /// Instructions and conditional branches worth covering: everything the
+    /// static analysis finds plus whatever was executed through computed
+    /// jumps. Jumps on an immediate condition always go the same way and are
+    /// not counted as branches.
+    fn targets(&self, program: &[isize]) -> (BTreeSet<usize>, BTreeSet<usize>) {
+        let cfg = analysis::analyze(program);
+        let instructions = cfg
+            .blocks
+            .values()
+            .flat_map(|block| block.lines.iter().map(Line::address))
+            .chain(self.hits.keys().copied())
+            .collect();
+        let branches = cfg
+            .blocks
+            .values()
+            .filter(|block| matches!(block.terminator, Terminator::Branch { .. }))
+            .filter_map(|block| block.lines.last().map(Line::address))
+            .chain(
+                self.branches
+                    .keys()
+                    .copied()
+                    .filter(|pc| program.get(*pc).is_some_and(|word| word / 100 % 10 != 1)),
+            )
+            .collect();
+        (instructions, branches)
+    }
+
+    fn branch(&self, pc: usize) -> BranchCount {
+        self.branches.get(&pc).copied().unwrap_or_default()
+    }
+
+    pub fn summary(&self, program: &[isize]) -> CoverageSummary {
+        let (instructions, branches) = self.targets(program);
+        CoverageSummary {
+            instructions_found: instructions.len(),
+            instructions_hit: instructions
+                .iter()
+                .filter(|pc| self.hits.contains_key(pc))
+                .count(),
+            branches_found: 2 * branches.len(),
+            branches_hit: branches
+                .iter()
+                .map(|pc| {
+                    let branch = self.branch(*pc);
+                    (branch.taken > 0) as usize + (branch.not_taken > 0) as usize
+                })
+                .sum(),
+        }
+    }
+
+    /// Address ranges no executed instruction covers.
+    pub fn uncovered(&self, program: &[isize]) -> Vec<Range<usize>> {
+        let mut covered = vec![false; program.len()];
+        for pc in self.hits.keys() {
+            let size = Opcode::try_from(program.get(*pc).copied().unwrap_or(0) % 100)
+                .map_or(1, |opcode| opcode.parameters() + 1);
+            let end = (*pc + size).min(program.len());
+            for covered in covered.iter_mut().take(end).skip(*pc) {
+                *covered = true;
+            }
+        }
+
+        let mut regions: Vec<Range<usize>> = Vec::new();
+        for (address, _) in covered.iter().enumerate().filter(|(_, c)| !**c) {
+            match regions.last_mut() {
+                Some(region) if region.end == address => region.end = address + 1,
+                _ => regions.push(address..address + 1),
+            }
+        }
+        regions
+    }
+
+    /// Disassembly of `program` with the hit count of every instruction in
+    /// front of it, `-` marking lines never executed.
+    pub fn annotate(&self, program: &[isize]) -> String {
+        let mut listing = String::new();
+        for line in disassembler::disassemble(program) {
+            let count = match self.hits.get(&line.address()) {
+                Some(count) => count.to_string(),
+                None => String::from("-"),
+            };
+            write!(listing, "{:>10}  {}", count, line).unwrap();
+            if let Some(branch) = self.branches.get(&line.address()) {
+                write!(
+                    listing,
+                    "  ; taken {}, not taken {}",
+                    branch.taken, branch.not_taken
+                )
+                .unwrap();
+            }
+            listing.push('\n');
+        }
+        listing
+    }
+
+    /// Coverage in the lcov tracefile format, with addresses standing in for
+    /// line numbers.
+    pub fn lcov(&self, program: &[isize], name: &str) -> String {
+        let mut lcov = String::new();
+        writeln!(lcov, "TN:").unwrap();
+        writeln!(lcov, "SF:{}", name).unwrap();
+
+        let (instructions, branches) = self.targets(program);
+        for pc in branches {
+            let branch = self.branch(pc);
+            let executed = self.hits.contains_key(&pc);
+            for (index, count) in [branch.taken, branch.not_taken].iter().enumerate() {
+                let count = if executed {
+                    count.to_string()
+                } else {
+                    String::from("-")
+                };
+                writeln!(lcov, "BRDA:{},0,{},{}", pc, index, count).unwrap();
+            }
+        }
+        for pc in instructions {
+            let count = self.hits.get(&pc).copied().unwrap_or(0);
+            writeln!(lcov, "DA:{},{}", pc, count).unwrap();
+        }
+
+        let summary = self.summary(program);
+        writeln!(lcov, "BRF:{}", summary.branches_found).unwrap();
+        writeln!(lcov, "BRH:{}", summary.branches_hit).unwrap();
+        writeln!(lcov, "LF:{}", summary.instructions_found).unwrap();
+        writeln!(lcov, "LH:{}", summary.instructions_hit).unwrap();
+        writeln!(lcov, "end_of_record").unwrap();
+        lcov
+    }
+}
+
+impl Intcode {
+    /// Starts recording executed addresses, discarding any previous coverage.
+    pub fn start_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    pub(crate) fn coverage_step(&mut self, pc: usize, instruction: isize) {
+        let next = self.pc;
+        if let Some(coverage) = self.coverage.as_mut() {
+            *coverage.hits.entry(pc).or_insert(0) += 1;
+            if matches!(instruction % 100, 5 | 6) {
+                let branch = coverage.branches.entry(pc).or_default();
+                if next == pc + 3 {
+                    branch.not_taken += 1;
+                } else {
+                    branch.taken += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_sided_branches_behind_computed_jumps_are_half_covered() {
+        let program = [
+            1101, 0, 7, 20, // add #0, #7 -> [20]
+            105, 1, 20, // jnz #1, [20]
+            1006, 21, 13, // jz [21], #13, only ever taken
+            99, 0, 0, // hlt
+            1105, 1, 16, // jnz #1, #16, unconditional
+            99, 0, 0, 0, 0, 0,
+        ];
+        let mut intcode = Intcode::new(&program);
+        intcode.start_coverage();
+        intcode.run().unwrap();
+        let coverage = intcode.take_coverage().unwrap();
+
+        let summary = coverage.summary(&program);
+        assert_eq!(summary.branches_found, 2);
+        assert_eq!(summary.branches_hit, 1);
+        assert!(coverage
+            .lcov(&program, "test")
+            .contains("BRDA:7,0,0,1\nBRDA:7,0,1,0\n"));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::instruction::{self, Mode, Opcode};
+use std::convert::TryFrom;
+use std::fmt;
+
+const DATA_PER_LINE: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Operand {
+    pub mode: Mode,
+    pub value: isize,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "[{}]", self.value),
+            Mode::Immediate => write!(f, "#{}", self.value),
+            Mode::Relative if self.value < 0 => write!(f, "[rb-{}]", self.value.unsigned_abs()),
+            Mode::Relative => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line {
+    Instruction {
+        address: usize,
+        opcode: Opcode,
+        operands: Vec<Operand>,
+    },
+    Data {
+        address: usize,
+        values: Vec<isize>,
+    },
+}
+
+impl Line {
+    pub fn address(&self) -> usize {
+        match self {
+            Line::Instruction { address, .. } | Line::Data { address, .. } => *address,
+        }
+    }
+
+    /// Number of memory words the line covers.
+    pub fn size(&self) -> usize {
+        match self {
+            Line::Instruction { operands, .. } => operands.len() + 1,
+            Line::Data { values, .. } => values.len(),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Instruction {
+                address,
+                opcode,
+                operands,
+            } => {
+                write!(f, "{:04}: {}", address, opcode.mnemonic().to_uppercase())?;
+
+                let (inputs, output) = if opcode.writes() {
+                    let (output, inputs) = operands.split_last().unwrap();
+                    (inputs, Some(output))
+                } else {
+                    (&operands[..], None)
+                };
+
+                for (i, operand) in inputs.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}", separator, operand)?;
+                }
+                if let Some(output) = output {
+                    write!(f, " -> {}", output)?;
+                }
+                Ok(())
+            }
+            Line::Data { address, values } => {
+                let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "{:04}: DATA {}", address, values.join(", "))
+            }
+        }
+    }
+}
+
+/// Decodes the instruction at `address`, or returns `None` if the words there
+/// are not something the VM could execute.
+pub fn decode_at(program: &[isize], address: usize) -> Option<(Opcode, Vec<Operand>)> {
+    let value = *program.get(address)?;
+    let (opcode, mode_1, mode_2, mode_3) = instruction::decode(value).ok()?;
+    let opcode = Opcode::try_from(opcode).ok()?;
+
+    let parameters = opcode.parameters();
+    let values = program.get(address + 1..address + 1 + parameters)?;
+    let operands: Vec<_> = [mode_1, mode_2, mode_3]
+        .iter()
+        .zip(values.iter())
+        .map(|(mode, value)| Operand {
+            mode: *mode,
+            value: *value,
+        })
+        .collect();
+
+    if opcode.writes() && operands.last().unwrap().mode == Mode::Immediate {
+        return None;
+    }
+
+    Some((opcode, operands))
+}
+
+pub fn disassemble(program: &[isize]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut address = 0;
+
+    while address < program.len() {
+        if let Some((opcode, operands)) = decode_at(program, address) {
+            let line = Line::Instruction {
+                address,
+                opcode,
+                operands,
+            };
+            address += line.size();
+            lines.push(line);
+            continue;
+        }
+
+        match lines.last_mut() {
+            Some(Line::Data { values, .. }) if values.len() < DATA_PER_LINE => {
+                values.push(program[address])
+            }
+            _ => lines.push(Line::Data {
+                address,
+                values: vec![program[address]],
+            }),
+        }
+        address += 1;
+    }
+
+    lines
+}
+
+pub fn listing(program: &[isize]) -> String {
+    disassemble(program)
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
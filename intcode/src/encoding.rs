@@ -0,0 +1,87 @@
+//! Variable-length integer encoding shared by the binary file formats.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeError(pub &'static str);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for DecodeError {}
+
+pub fn write_unsigned(writer: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+pub fn write_signed(writer: &mut impl Write, value: isize) -> io::Result<()> {
+    let value = value as i64;
+    write_unsigned(writer, ((value << 1) ^ (value >> 63)) as u64)
+}
+
+pub fn write_values<'a>(
+    writer: &mut impl Write,
+    values: impl ExactSizeIterator<Item = &'a isize>,
+) -> io::Result<()> {
+    write_unsigned(writer, values.len() as u64)?;
+    for value in values {
+        write_signed(writer, *value)?;
+    }
+    Ok(())
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn unsigned(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = match self.bytes.split_first() {
+                Some(split) => split,
+                None => return Err(DecodeError("unexpected end of data")),
+            };
+            self.bytes = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError("varint too long"))
+    }
+
+    pub fn signed(&mut self) -> Result<isize, DecodeError> {
+        let value = self.unsigned()?;
+        Ok(((value >> 1) as i64 ^ -((value & 1) as i64)) as isize)
+    }
+
+    pub fn usize(&mut self) -> Result<usize, DecodeError> {
+        Ok(self.unsigned()? as usize)
+    }
+
+    pub fn values(&mut self) -> Result<Vec<isize>, DecodeError> {
+        let len = self.usize()?;
+        (0..len).map(|_| self.signed()).collect()
+    }
+}
@@ -0,0 +1,60 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCause {
+    UnsupportedOpcode(isize),
+    UnsupportedMode(isize),
+    NegativeAddress(isize),
+    WriteInImmediateMode,
+    /// Arithmetic result does not fit the word, only raised in checked mode.
+    Overflow,
+    /// Instruction too large to decode, only raised by `word::Machine`.
+    InstructionOutOfRange,
+    /// Address or relative offset too large for `isize`, only raised by
+    /// `word::Machine`.
+    AddressOutOfRange,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntcodeError {
+    pub pc: usize,
+    pub instruction: isize,
+    pub cause: ErrorCause,
+}
+
+impl IntcodeError {
+    pub fn new(pc: usize, instruction: isize, cause: ErrorCause) -> Self {
+        Self {
+            pc,
+            instruction,
+            cause,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorCause::UnsupportedOpcode(opcode) => write!(f, "unsupported opcode {}", opcode),
+            ErrorCause::UnsupportedMode(mode) => write!(f, "unsupported mode {}", mode),
+            ErrorCause::NegativeAddress(address) => write!(f, "negative address {}", address),
+            ErrorCause::WriteInImmediateMode => write!(f, "write in immediate mode"),
+            ErrorCause::Overflow => write!(f, "arithmetic overflow"),
+            ErrorCause::InstructionOutOfRange => write!(f, "instruction out of range"),
+            ErrorCause::AddressOutOfRange => write!(f, "address out of range"),
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at pc {} (instruction {})",
+            self.cause, self.pc, self.instruction
+        )
+    }
+}
+
+impl Error for IntcodeError {}
@@ -0,0 +1,257 @@
+use crate::trace::IoEvent;
+use crate::Intcode;
+use std::collections::VecDeque;
+
+/// What an executed instruction changed, so it can be undone. Built-in
+/// instructions make at most one write and one I/O event, custom ones any
+/// number.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct UndoRecord {
+    pc: usize,
+    relative_base: isize,
+    /// Addresses written, with the values they held before.
+    writes: Vec<(usize, isize)>,
+    io: Vec<IoEvent>,
+}
+
+impl UndoRecord {
+    pub(crate) fn new(pc: usize, relative_base: isize) -> Self {
+        Self {
+            pc,
+            relative_base,
+            writes: Vec::new(),
+            io: Vec::new(),
+        }
+    }
+}
+
+/// Undo log of the most recently executed instructions, oldest dropped first
+/// once `capacity` is reached.
+#[derive(Clone, Debug)]
+pub(crate) struct History {
+    records: VecDeque<UndoRecord>,
+    capacity: usize,
+}
+
+impl History {
+    pub(crate) fn clear(&mut self) {
+        self.records.clear();
+    }
+}
+
+impl Intcode {
+    /// Starts keeping an undo log of up to `capacity` instructions so the
+    /// machine can be stepped backwards. A capacity of 0 disables the log.
+    pub fn enable_history(&mut self, capacity: usize) {
+        if capacity == 0 {
+            return self.disable_history();
+        }
+        self.history = Some(History {
+            records: VecDeque::new(),
+            capacity,
+        });
+    }
+
+    pub fn disable_history(&mut self) {
+        self.history = None;
+        self.undo_record = None;
+    }
+
+    pub fn history_enabled(&self) -> bool {
+        self.history.is_some()
+    }
+
+    /// Number of instructions that can currently be undone.
+    pub fn history_len(&self) -> usize {
+        self.history
+            .as_ref()
+            .map_or(0, |history| history.records.len())
+    }
+
+    /// Undoes the most recently executed instruction. Returns `false` when
+    /// there is nothing left to undo.
+    ///
+    /// An undone `out` only removes its value if it is still at the back of
+    /// the output queue, so outputs already taken by the host stay taken.
+    /// Values exchanged through `run_with_io` or `step_with_io` are not
+    /// restored, only the machine state is rewound.
+    pub fn step_back(&mut self) -> bool {
+        let record = match self.history.as_mut().and_then(|h| h.records.pop_back()) {
+            Some(record) => record,
+            None => return false,
+        };
+
+        for (address, old_value) in record.writes.into_iter().rev() {
+            self.write_to_memory(address, old_value);
+        }
+        for event in record.io.into_iter().rev() {
+            match event {
+                IoEvent::Input(value) => self.input.push_front(value),
+                IoEvent::Output(value) if self.output.back() == Some(&value) => {
+                    self.output.pop_back();
+                }
+                _ => (),
+            }
+        }
+
+        self.pc = record.pc;
+        self.relative_base = record.relative_base;
+        self.finished = false;
+        self.awaits_input = false;
+        self.watch_hits.clear();
+        true
+    }
+
+    /// Steps backwards until the instruction that most recently wrote
+    /// `address` has been undone, leaving the pc on it. Returns that pc, or
+    /// `None` (with the whole history undone) if no recorded instruction
+    /// wrote there.
+    pub fn run_back_to_write(&mut self, address: usize) -> Option<usize> {
+        loop {
+            let wrote = self
+                .history
+                .as_ref()
+                .and_then(|history| history.records.back())
+                .map(|record| record.writes.iter().any(|(a, _)| *a == address))?;
+            self.step_back();
+            if wrote {
+                return Some(self.pc);
+            }
+        }
+    }
+
+    pub(crate) fn undo_write(&mut self, address: usize) {
+        if let Some(record) = self.undo_record.as_mut() {
+            record.writes.push((address, self.memory.get(address)));
+        }
+    }
+
+    /// Only queue traffic can be taken back, values exchanged with an
+    /// external `IntcodeIo` are left alone when stepping back.
+    pub(crate) fn undo_io(&mut self, event: IoEvent) {
+        if !self.queued_io {
+            return;
+        }
+        if let Some(record) = self.undo_record.as_mut() {
+            record.io.push(event);
+        }
+    }
+
+    pub(crate) fn commit_undo_record(&mut self) {
+        if let (Some(history), Some(record)) = (self.history.as_mut(), self.undo_record.take()) {
+            if history.records.len() >= history.capacity {
+                history.records.pop_front();
+            }
+            history.records.push_back(record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction_set::{CustomOpcode, InstructionSet};
+    use crate::StepOutcome;
+
+    #[test]
+    fn steps_back_over_queue_io_and_halt() {
+        // in [9], out [9], hlt
+        let mut intcode = Intcode::new(&[3, 9, 4, 9, 99, 0, 0, 0, 0, 0]);
+        intcode.enable_history(10);
+        intcode.add_input(5);
+        assert_eq!(intcode.run().unwrap(), StepOutcome::Finished);
+        assert_eq!(intcode.get_output(), &[5]);
+
+        assert!(intcode.step_back());
+        assert!(!intcode.finished());
+        assert_eq!(intcode.pc(), 4);
+
+        assert!(intcode.step_back());
+        assert_eq!(intcode.pc(), 2);
+        assert!(intcode.get_output().is_empty());
+
+        assert!(intcode.step_back());
+        assert_eq!(intcode.pc(), 0);
+        assert_eq!(intcode.get_input(), &[5]);
+        assert_eq!(intcode.read_from_memory(9), 0);
+        assert!(!intcode.step_back());
+
+        assert_eq!(intcode.run().unwrap(), StepOutcome::Finished);
+        assert_eq!(intcode.get_output(), &[5]);
+    }
+
+    #[test]
+    fn runs_back_to_the_last_write() {
+        let mut intcode = Intcode::new(&[
+            1101, 1, 2, 20, // add #1, #2 -> [20]
+            1101, 3, 4, 21, // add #3, #4 -> [21]
+            1101, 5, 6, 20, // add #5, #6 -> [20]
+            99,
+        ]);
+        intcode.enable_history(10);
+        intcode.run().unwrap();
+
+        assert_eq!(intcode.run_back_to_write(21), Some(4));
+        assert_eq!(intcode.read_from_memory(20), 3);
+        assert_eq!(intcode.read_from_memory(21), 0);
+        assert_eq!(intcode.history_len(), 1);
+
+        assert_eq!(intcode.run_back_to_write(30), None);
+        assert_eq!(intcode.pc(), 0);
+        assert_eq!(intcode.read_from_memory(20), 0);
+        assert_eq!(intcode.history_len(), 0);
+    }
+
+    #[test]
+    fn keeps_at_most_capacity_records() {
+        let mut intcode = Intcode::new(&[1101, 1, 2, 20, 1101, 3, 4, 21, 99]);
+        intcode.enable_history(2);
+        intcode.run().unwrap();
+        assert_eq!(intcode.history_len(), 2);
+
+        assert!(intcode.step_back());
+        assert!(intcode.step_back());
+        assert!(!intcode.step_back());
+        assert_eq!(intcode.pc(), 4);
+        assert_eq!(intcode.read_from_memory(20), 3);
+    }
+
+    #[test]
+    fn steps_back_over_a_custom_instruction() {
+        let opcode = CustomOpcode::new("fill", &[], |context| {
+            context.store(10, 7);
+            context.store(11, 8);
+            context.store(10, 9);
+            context.output(1);
+            context.output(2);
+            Ok(())
+        })
+        .unwrap();
+        let mut program = vec![0; 12];
+        program[0] = 42;
+        program[1] = 99;
+        program[10] = 5;
+        program[11] = 6;
+        let mut intcode = Intcode::new(&program);
+        intcode.set_instruction_set(InstructionSet::full().with(42, opcode));
+        intcode.enable_history(10);
+
+        intcode.execute_single_instruction().unwrap();
+        assert_eq!(intcode.read_from_memory(10), 9);
+        assert_eq!(intcode.get_output(), &[1, 2]);
+
+        assert!(intcode.step_back());
+        assert_eq!(intcode.pc(), 0);
+        assert_eq!(intcode.read_from_memory(10), 5);
+        assert_eq!(intcode.read_from_memory(11), 6);
+        assert!(intcode.get_output().is_empty());
+    }
+
+    #[test]
+    fn nothing_to_undo_without_history() {
+        let mut intcode = Intcode::new(&[1101, 1, 2, 20, 99]);
+        intcode.run().unwrap();
+        assert!(!intcode.history_enabled());
+        assert!(!intcode.step_back());
+    }
+}
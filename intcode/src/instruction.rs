@@ -0,0 +1,147 @@
+use crate::error::ErrorCause;
+use std::convert::TryFrom;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl TryFrom<isize> for Mode {
+    type Error = ErrorCause;
+
+    fn try_from(item: isize) -> Result<Self, Self::Error> {
+        match item {
+            0 => Ok(Mode::Position),
+            1 => Ok(Mode::Immediate),
+            2 => Ok(Mode::Relative),
+            _ => Err(ErrorCause::UnsupportedMode(item)),
+        }
+    }
+}
+
+impl Mode {
+    pub fn code(self) -> isize {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    Add,
+    Mul,
+    In,
+    Out,
+    Jnz,
+    Jz,
+    Lt,
+    Eq,
+    Arb,
+    Hlt,
+}
+
+impl TryFrom<isize> for Opcode {
+    type Error = ErrorCause;
+
+    fn try_from(item: isize) -> Result<Self, Self::Error> {
+        match item {
+            1 => Ok(Opcode::Add),
+            2 => Ok(Opcode::Mul),
+            3 => Ok(Opcode::In),
+            4 => Ok(Opcode::Out),
+            5 => Ok(Opcode::Jnz),
+            6 => Ok(Opcode::Jz),
+            7 => Ok(Opcode::Lt),
+            8 => Ok(Opcode::Eq),
+            9 => Ok(Opcode::Arb),
+            99 => Ok(Opcode::Hlt),
+            _ => Err(ErrorCause::UnsupportedOpcode(item)),
+        }
+    }
+}
+
+impl Opcode {
+    pub const ALL: [Opcode; 10] = [
+        Opcode::Add,
+        Opcode::Mul,
+        Opcode::In,
+        Opcode::Out,
+        Opcode::Jnz,
+        Opcode::Jz,
+        Opcode::Lt,
+        Opcode::Eq,
+        Opcode::Arb,
+        Opcode::Hlt,
+    ];
+
+    pub fn code(self) -> isize {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Mul => 2,
+            Opcode::In => 3,
+            Opcode::Out => 4,
+            Opcode::Jnz => 5,
+            Opcode::Jz => 6,
+            Opcode::Lt => 7,
+            Opcode::Eq => 8,
+            Opcode::Arb => 9,
+            Opcode::Hlt => 99,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Mul => "mul",
+            Opcode::In => "in",
+            Opcode::Out => "out",
+            Opcode::Jnz => "jnz",
+            Opcode::Jz => "jz",
+            Opcode::Lt => "lt",
+            Opcode::Eq => "eq",
+            Opcode::Arb => "arb",
+            Opcode::Hlt => "hlt",
+        }
+    }
+
+    /// Number of parameters the instruction reads from.
+    pub fn inputs(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::Jnz | Opcode::Jz | Opcode::Lt | Opcode::Eq => 2,
+            Opcode::Out | Opcode::Arb => 1,
+            Opcode::In | Opcode::Hlt => 0,
+        }
+    }
+
+    /// Whether the last parameter is an address the instruction writes to.
+    pub fn writes(self) -> bool {
+        matches!(
+            self,
+            Opcode::Add | Opcode::Mul | Opcode::In | Opcode::Lt | Opcode::Eq
+        )
+    }
+
+    pub fn parameters(self) -> usize {
+        self.inputs() + self.writes() as usize
+    }
+}
+
+/// Splits a raw instruction into its opcode and the modes of its three parameters.
+pub fn decode(value: isize) -> Result<(isize, Mode, Mode, Mode), ErrorCause> {
+    let opcode = value % 100;
+    let mode_1 = (value % 1_000) / 100;
+    let mode_2 = (value % 10_000) / 1_000;
+    let mode_3 = value / 10_000;
+
+    Ok((
+        opcode,
+        Mode::try_from(mode_1)?,
+        Mode::try_from(mode_2)?,
+        Mode::try_from(mode_3)?,
+    ))
+}
@@ -0,0 +1,294 @@
+use crate::analysis;
+use crate::instruction::{Mode, Opcode};
+use crate::io::IntcodeIo;
+use crate::trace::IoEvent;
+use crate::{ErrorCause, Intcode};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+type Handler = Arc<dyn Fn(&mut Context) -> Result<(), ErrorCause> + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parameter {
+    /// A value, in any mode.
+    Read,
+    /// An address to write to, in position or relative mode.
+    Write,
+}
+
+/// A custom instruction declared with more parameters than an instruction word
+/// has modes for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TooManyParameters(pub usize);
+
+impl fmt::Display for TooManyParameters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} parameters given, at most three are supported",
+            self.0
+        )
+    }
+}
+
+impl Error for TooManyParameters {}
+
+/// Instruction added by the host. Its handler runs once the parameters are
+/// resolved, with the program counter already past the instruction.
+#[derive(Clone)]
+pub struct CustomOpcode {
+    pub mnemonic: String,
+    pub parameters: Vec<Parameter>,
+    handler: Handler,
+}
+
+impl CustomOpcode {
+    /// Fails if there are more than three parameters, which is all an
+    /// instruction word has modes for.
+    pub fn new(
+        mnemonic: &str,
+        parameters: &[Parameter],
+        handler: impl Fn(&mut Context) -> Result<(), ErrorCause> + Send + Sync + 'static,
+    ) -> Result<Self, TooManyParameters> {
+        if parameters.len() > 3 {
+            return Err(TooManyParameters(parameters.len()));
+        }
+        Ok(Self {
+            mnemonic: mnemonic.to_string(),
+            parameters: parameters.to_vec(),
+            handler: Arc::new(handler),
+        })
+    }
+}
+
+impl fmt::Debug for CustomOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CustomOpcode")
+            .field("mnemonic", &self.mnemonic)
+            .field("parameters", &self.parameters)
+            .finish()
+    }
+}
+
+/// What a custom instruction's handler can see and change.
+pub struct Context<'a> {
+    intcode: &'a mut Intcode,
+    io: &'a mut dyn IntcodeIo,
+    arguments: Vec<isize>,
+}
+
+impl Context<'_> {
+    /// Values of read parameters and addresses of write parameters, in order.
+    pub fn arguments(&self) -> &[isize] {
+        &self.arguments
+    }
+
+    /// `None` if the instruction has no parameter at `index`.
+    pub fn argument(&self, index: usize) -> Option<isize> {
+        self.arguments.get(index).copied()
+    }
+
+    pub fn load(&mut self, address: usize) -> isize {
+        self.intcode.load(address)
+    }
+
+    pub fn store(&mut self, address: usize, value: isize) {
+        self.intcode.store(address, value)
+    }
+
+    /// Takes a value from the input, if there is one.
+    pub fn input(&mut self) -> Option<isize> {
+        let input = self.intcode.profile_input(self.io)?;
+        self.intcode.trace_io(IoEvent::Input(input));
+        self.intcode.undo_io(IoEvent::Input(input));
+        Some(input)
+    }
+
+    pub fn output(&mut self, value: isize) {
+        self.intcode.trace_io(IoEvent::Output(value));
+        self.intcode.undo_io(IoEvent::Output(value));
+        self.io.write(value);
+    }
+
+    /// Suspends the machine so the instruction runs again once input arrives.
+    /// Must be called before the handler changes anything.
+    pub fn wait_for_input(&mut self) {
+        self.intcode.pc = self.intcode.instruction_pc;
+        self.intcode.awaits_input = true;
+    }
+
+    pub fn jump(&mut self, address: usize) {
+        self.intcode.pc = address;
+    }
+
+    pub fn relative_base(&self) -> isize {
+        self.intcode.relative_base
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: isize) {
+        self.intcode.relative_base = relative_base;
+    }
+
+    pub fn halt(&mut self) {
+        self.intcode.finished = true;
+    }
+}
+
+/// Opcode used by a program that is not part of an instruction set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub address: usize,
+    pub opcode: isize,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}: opcode {} is not allowed",
+            self.address, self.opcode
+        )
+    }
+}
+
+/// The opcodes a machine accepts: any subset of the built-in ones plus custom
+/// instructions registered by the host.
+#[derive(Clone, Debug)]
+pub struct InstructionSet {
+    builtin: BTreeSet<isize>,
+    custom: BTreeMap<isize, Arc<CustomOpcode>>,
+}
+
+impl Default for InstructionSet {
+    fn default() -> Self {
+        Self::only(&Opcode::ALL)
+    }
+}
+
+impl InstructionSet {
+    /// Every built-in opcode, as of day 9.
+    pub fn full() -> Self {
+        Self::default()
+    }
+
+    pub fn only(opcodes: &[Opcode]) -> Self {
+        Self {
+            builtin: opcodes.iter().map(|opcode| opcode.code()).collect(),
+            custom: BTreeMap::new(),
+        }
+    }
+
+    /// `add`, `mul` and `hlt`, as introduced on day 2.
+    pub fn day02() -> Self {
+        Self::only(&[Opcode::Add, Opcode::Mul, Opcode::Hlt])
+    }
+
+    /// Everything but `arb`, as of day 5.
+    pub fn day05() -> Self {
+        let mut set = Self::full();
+        set.builtin.remove(&Opcode::Arb.code());
+        set
+    }
+
+    /// Adds a custom instruction, replacing a built-in or custom one with the
+    /// same code.
+    pub fn register(&mut self, code: isize, opcode: CustomOpcode) {
+        self.builtin.remove(&code);
+        self.custom.insert(code, Arc::new(opcode));
+    }
+
+    pub fn with(mut self, code: isize, opcode: CustomOpcode) -> Self {
+        self.register(code, opcode);
+        self
+    }
+
+    pub fn allows(&self, code: isize) -> bool {
+        self.builtin.contains(&code) || self.custom.contains_key(&code)
+    }
+
+    pub fn custom(&self, code: isize) -> Option<&CustomOpcode> {
+        self.custom.get(&code).map(Arc::as_ref)
+    }
+
+    /// Checks the instructions reachable from address 0 against the set.
+    /// Custom instructions end the walk, since their length and effect on
+    /// control flow are not known statically. Code only reached through
+    /// computed jumps or written at run time is not checked here; the machine
+    /// still fails with `UnsupportedOpcode` when it gets there.
+    pub fn validate(&self, program: &[isize]) -> Result<(), Vec<Violation>> {
+        let cfg = analysis::analyze(program);
+        let instructions = cfg
+            .blocks
+            .values()
+            .flat_map(|block| block.lines.iter().map(|line| line.address()))
+            .chain(cfg.invalid.iter().copied());
+
+        let violations: Vec<_> = instructions
+            .filter_map(|address| {
+                let opcode = program.get(address).copied().unwrap_or(0) % 100;
+                if self.allows(opcode) {
+                    None
+                } else {
+                    Some(Violation { address, opcode })
+                }
+            })
+            .collect();
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+impl Intcode {
+    /// Runs with `set` instead of the built-in instructions. The set can be
+    /// shared between machines.
+    pub fn set_instruction_set(&mut self, set: impl Into<Arc<InstructionSet>>) {
+        self.instruction_set = Some(set.into());
+    }
+
+    pub fn instruction_set(&self) -> Option<&InstructionSet> {
+        self.instruction_set.as_deref()
+    }
+
+    /// Executes a custom instruction, or fails if the set does not allow a
+    /// built-in one.
+    pub(crate) fn process_custom(
+        &mut self,
+        opcode: isize,
+        modes: [Mode; 3],
+        io: &mut dyn IntcodeIo,
+    ) -> Result<bool, ErrorCause> {
+        let set = match self.instruction_set.as_ref() {
+            Some(set) => set,
+            None => return Ok(false),
+        };
+        if !set.allows(opcode) {
+            return Err(ErrorCause::UnsupportedOpcode(opcode));
+        }
+        let custom = match set.custom.get(&opcode) {
+            Some(custom) => Arc::clone(custom),
+            None => return Ok(false),
+        };
+
+        let mut arguments = Vec::with_capacity(custom.parameters.len());
+        for (parameter, mode) in custom.parameters.iter().zip(modes.iter()) {
+            arguments.push(match parameter {
+                Parameter::Read => self.read(*mode)?,
+                Parameter::Write => self.write_address(*mode)? as isize,
+            });
+        }
+
+        let mut context = Context {
+            intcode: self,
+            io,
+            arguments,
+        };
+        (custom.handler)(&mut context)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+
+/// Where a machine's `in` instructions read from and `out` instructions
+/// write to.
+pub trait IntcodeIo {
+    /// Returns the next input, or `None` if none is available yet, in which
+    /// case the machine stops with `StepOutcome::AwaitsInput` and retries the
+    /// same `in` instruction when resumed.
+    fn read(&mut self) -> Option<isize>;
+
+    fn write(&mut self, value: isize);
+}
+
+/// The built-in behaviour: inputs and outputs are buffered in queues.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueueIo {
+    pub input: VecDeque<isize>,
+    pub output: VecDeque<isize>,
+}
+
+impl IntcodeIo for QueueIo {
+    fn read(&mut self) -> Option<isize> {
+        self.input.pop_front()
+    }
+
+    fn write(&mut self, value: isize) {
+        self.output.push_back(value);
+    }
+}
+
+/// Reads and writes through a pair of closures.
+pub struct FnIo<R, W> {
+    read: R,
+    write: W,
+}
+
+impl<R, W> FnIo<R, W>
+where
+    R: FnMut() -> Option<isize>,
+    W: FnMut(isize),
+{
+    pub fn new(read: R, write: W) -> Self {
+        Self { read, write }
+    }
+}
+
+impl<R, W> IntcodeIo for FnIo<R, W>
+where
+    R: FnMut() -> Option<isize>,
+    W: FnMut(isize),
+{
+    fn read(&mut self) -> Option<isize> {
+        (self.read)()
+    }
+
+    fn write(&mut self, value: isize) {
+        (self.write)(value)
+    }
+}
+
+/// Takes inputs from an iterator and collects outputs.
+pub struct IterIo<I> {
+    input: I,
+    pub output: Vec<isize>,
+}
+
+impl<I: Iterator<Item = isize>> IterIo<I> {
+    pub fn new(input: impl IntoIterator<IntoIter = I, Item = isize>) -> Self {
+        Self {
+            input: input.into_iter(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = isize>> IntcodeIo for IterIo<I> {
+    fn read(&mut self) -> Option<isize> {
+        self.input.next()
+    }
+
+    fn write(&mut self, value: isize) {
+        self.output.push(value);
+    }
+}
+
+/// Connects a machine to channels. A blocking reader waits for the next
+/// value; a non-blocking one reports "would block" when the channel is empty.
+/// Outputs sent after the receiving side hung up are dropped.
+pub struct ChannelIo {
+    input: Receiver<isize>,
+    output: Sender<isize>,
+    blocking: bool,
+}
+
+impl ChannelIo {
+    pub fn new(input: Receiver<isize>, output: Sender<isize>) -> Self {
+        Self {
+            input,
+            output,
+            blocking: false,
+        }
+    }
+
+    pub fn blocking(input: Receiver<isize>, output: Sender<isize>) -> Self {
+        Self {
+            input,
+            output,
+            blocking: true,
+        }
+    }
+}
+
+impl IntcodeIo for ChannelIo {
+    fn read(&mut self) -> Option<isize> {
+        if self.blocking {
+            return self.input.recv().ok();
+        }
+        match self.input.try_recv() {
+            Ok(value) => Some(value),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None,
+        }
+    }
+
+    fn write(&mut self, value: isize) {
+        let _ = self.output.send(value);
+    }
+}
@@ -1,230 +1,494 @@
-use std::collections::HashMap;
+pub mod amplifier;
+pub mod analysis;
+pub mod ascii;
+pub mod assembler;
+pub mod asynchronous;
+mod budget;
+pub mod cached;
+pub mod coverage;
+pub mod disassembler;
+mod encoding;
+mod error;
+mod history;
+pub mod instruction;
+pub mod instruction_set;
+pub mod io;
+pub mod memory;
+pub mod network;
+pub mod profile;
+pub mod program;
+pub mod self_modifying;
+mod semantics;
+mod state;
+pub mod threaded;
+pub mod trace;
+mod watch;
+pub mod word;
+
+pub use budget::Budget;
+pub use cached::CachedIntcode;
+pub use encoding::DecodeError;
+pub use error::{ErrorCause, IntcodeError};
+pub use instruction::{Mode, Opcode};
+pub use io::IntcodeIo;
+pub use program::Program;
+pub use state::{StateError, StateFormat};
+pub use watch::{MemoryAccess, WatchHit, WatchKind, Watchpoint};
+pub use word::{Machine, MachineError, Word};
+
+use coverage::Coverage;
+use history::{History, UndoRecord};
+use instruction_set::InstructionSet;
+use io::QueueIo;
+use memory::Memory;
+use profile::Profile;
+use self_modifying::{CodeWrite, SelfModification};
 use std::collections::VecDeque;
-
-enum Mode {
-    Position,
-    Immediate,
-    Relative,
+use std::mem;
+use std::ops::Range;
+use std::sync::Arc;
+use trace::{IoEvent, Trace, TraceEntry};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepOutcome {
+    Executed,
+    AwaitsInput,
+    Finished,
+    Watchpoint(WatchHit),
+    BudgetExhausted,
+    CodeWrite(CodeWrite),
 }
 
-impl From<isize> for Mode {
-    fn from(item: isize) -> Self {
-        match item {
-            0 => Mode::Position,
-            1 => Mode::Immediate,
-            2 => Mode::Relative,
-            _ => panic!("unsupported mode"),
-        }
-    }
+/// Machine state captured by `Intcode::snapshot`. Memory pages are shared with
+/// the machine until one side writes to them.
+#[derive(Clone)]
+pub struct Snapshot {
+    memory: Memory,
+    pc: usize,
+    relative_base: isize,
+    input: VecDeque<isize>,
+    output: VecDeque<isize>,
+    finished: bool,
+    awaits_input: bool,
 }
 
 #[derive(Clone)]
 pub struct Intcode {
-    memory: HashMap<usize, isize>,
+    memory: Memory,
     pc: usize,
     relative_base: isize,
     input: VecDeque<isize>,
     output: VecDeque<isize>,
     finished: bool,
     awaits_input: bool,
+    instruction_pc: usize,
+    /// Whether the running step talks to the internal queues.
+    queued_io: bool,
+    watchpoints: Vec<Watchpoint>,
+    watch_hits: Vec<WatchHit>,
+    trace: Option<Trace>,
+    trace_entry: Option<TraceEntry>,
+    history: Option<History>,
+    undo_record: Option<UndoRecord>,
+    profile: Option<Profile>,
+    coverage: Option<Coverage>,
+    checked: bool,
+    instruction_set: Option<Arc<InstructionSet>>,
+    self_modification: Option<SelfModification>,
 }
 
 impl Intcode {
     pub fn new(program: &[isize]) -> Self {
-        let mut initial_memory = HashMap::new();
-        for (address, byte) in program.iter().enumerate() {
-            initial_memory.insert(address, *byte);
-        }
-
         Self {
-            memory: initial_memory,
+            memory: Memory::new(program),
             pc: 0,
             relative_base: 0,
             input: VecDeque::new(),
             output: VecDeque::new(),
             finished: false,
             awaits_input: false,
+            instruction_pc: 0,
+            queued_io: false,
+            watchpoints: Vec::new(),
+            watch_hits: Vec::new(),
+            trace: None,
+            trace_entry: None,
+            history: None,
+            undo_record: None,
+            profile: None,
+            coverage: None,
+            checked: false,
+            instruction_set: None,
+            self_modification: None,
         }
     }
 
-    pub fn run(&mut self) {
-        while !self.finished && !self.awaits_input {
-            self.execute_single_instruction();
+    pub fn run(&mut self) -> Result<StepOutcome, IntcodeError> {
+        if self.awaits_input {
+            return Ok(StepOutcome::AwaitsInput);
         }
+        self.with_queues(|intcode, io| intcode.run_with_io(io))
     }
 
-    pub fn execute_single_instruction(&mut self) {
-        if !self.finished && !self.awaits_input {
-            //self.awaits_input = false;
-            let (opcode, mode_1, mode_2, mode_3) = self.decode_instruction();
-            match opcode {
-                1 => self.process_1(mode_1, mode_2, mode_3),
-                2 => self.process_2(mode_1, mode_2, mode_3),
-                3 => self.process_3(mode_1),
-                4 => self.process_4(mode_1),
-                5 => self.process_5(mode_1, mode_2),
-                6 => self.process_6(mode_1, mode_2),
-                7 => self.process_7(mode_1, mode_2, mode_3),
-                8 => self.process_8(mode_1, mode_2, mode_3),
-                9 => self.process_9(mode_1),
-                99 => self.process_99(),
-                _ => panic!("unsupported opcode"),
+    pub fn execute_single_instruction(&mut self) -> Result<StepOutcome, IntcodeError> {
+        if self.awaits_input {
+            return Ok(StepOutcome::AwaitsInput);
+        }
+        self.with_queues(|intcode, io| intcode.step_with_io(io))
+    }
+
+    /// Runs until the program halts, `io` has no input ready, a watchpoint
+    /// fires or a code write stops it. The internal input and output queues
+    /// are not used.
+    pub fn run_with_io(&mut self, io: &mut dyn IntcodeIo) -> Result<StepOutcome, IntcodeError> {
+        loop {
+            match self.step_with_io(io)? {
+                StepOutcome::Executed => continue,
+                outcome => return Ok(outcome),
             }
         }
     }
 
-    fn decode_instruction(&mut self) -> (isize, Mode, Mode, Mode) {
+    /// Executes one instruction with `in` and `out` going through `io`. An `in`
+    /// that previously found no input is retried.
+    pub fn step_with_io(&mut self, io: &mut dyn IntcodeIo) -> Result<StepOutcome, IntcodeError> {
+        if self.finished {
+            return Ok(StepOutcome::Finished);
+        }
+        self.awaits_input = false;
+
+        let pc = self.pc;
+        self.instruction_pc = pc;
+        self.watch_hits.clear();
+        let instruction = self.read_from_memory(pc);
+        if self.trace.is_some() {
+            self.trace_entry = Some(TraceEntry::new(pc, instruction));
+        }
+        if self.history.is_some() {
+            self.undo_record = Some(UndoRecord::new(pc, self.relative_base));
+        }
+        let result = self
+            .decode_instruction()
+            .and_then(|(opcode, mode_1, mode_2, mode_3)| {
+                self.self_modification_step(pc, opcode);
+                if self.instruction_set.is_some()
+                    && self.process_custom(opcode, [mode_1, mode_2, mode_3], io)?
+                {
+                    return Ok(());
+                }
+                match opcode {
+                    1 => self.process_1(mode_1, mode_2, mode_3),
+                    2 => self.process_2(mode_1, mode_2, mode_3),
+                    3 => self.process_3(mode_1, io),
+                    4 => self.process_4(mode_1, io),
+                    5 => self.process_5(mode_1, mode_2),
+                    6 => self.process_6(mode_1, mode_2),
+                    7 => self.process_7(mode_1, mode_2, mode_3),
+                    8 => self.process_8(mode_1, mode_2, mode_3),
+                    9 => self.process_9(mode_1),
+                    99 => self.process_99(),
+                    _ => Err(ErrorCause::UnsupportedOpcode(opcode)),
+                }
+            });
+
+        let entry = self.trace_entry.take();
+        if let (Ok(()), false, Some(trace), Some(entry)) =
+            (&result, self.awaits_input, self.trace.as_mut(), entry)
+        {
+            trace.entries.push(entry);
+        }
+        if result.is_ok() && !self.awaits_input {
+            self.commit_undo_record();
+            self.coverage_step(pc, instruction);
+            self.self_modification_executed();
+        } else {
+            self.undo_record = None;
+        }
+        if result.is_ok() {
+            self.profile_step(pc, instruction);
+        }
+
+        match result {
+            Ok(()) if self.finished => Ok(StepOutcome::Finished),
+            Ok(()) if self.awaits_input => Ok(StepOutcome::AwaitsInput),
+            Ok(()) => match (
+                self.self_modification_hit(),
+                self.watch_hits.first().copied(),
+            ) {
+                (Some(write), _) => Ok(StepOutcome::CodeWrite(write)),
+                (None, Some(hit)) => Ok(StepOutcome::Watchpoint(hit)),
+                (None, None) => Ok(StepOutcome::Executed),
+            },
+            Err(cause) => {
+                self.pc = pc;
+                self.watch_hits.clear();
+                Err(IntcodeError::new(pc, instruction, cause))
+            }
+        }
+    }
+
+    fn with_queues<T>(&mut self, f: impl FnOnce(&mut Self, &mut QueueIo) -> T) -> T {
+        let mut io = QueueIo {
+            input: mem::take(&mut self.input),
+            output: mem::take(&mut self.output),
+        };
+        self.queued_io = true;
+        let result = f(self, &mut io);
+        self.queued_io = false;
+        self.input = io.input;
+        self.output = io.output;
+        result
+    }
+
+    fn decode_instruction(&mut self) -> Result<(isize, Mode, Mode, Mode), ErrorCause> {
         let value = self.read_from_memory(self.pc);
         self.pc += 1;
 
-        let opcode = value % 100;
-        let mode_1 = (value % 1_000) / 100;
-        let mode_2 = (value % 10_000) / 1_000;
-        let mode_3 = value / 10_000;
-
-        (
-            opcode,
-            Mode::from(mode_1),
-            Mode::from(mode_2),
-            Mode::from(mode_3),
-        )
+        semantics::decode(&value)
     }
 
-    fn process_1(&mut self, mode_1: Mode, mode_2: Mode, mode_3: Mode) {
-        let input_1 = self.read(mode_1);
-        let input_2 = self.read(mode_2);
-        let result = input_1 + input_2;
-        self.write(mode_3, result);
+    fn process_1(&mut self, mode_1: Mode, mode_2: Mode, mode_3: Mode) -> Result<(), ErrorCause> {
+        let input_1 = self.read(mode_1)?;
+        let input_2 = self.read(mode_2)?;
+        let result = semantics::evaluate(Opcode::Add, &input_1, &input_2, self.checked)?;
+        self.write(mode_3, result)
     }
 
-    fn process_2(&mut self, mode_1: Mode, mode_2: Mode, mode_3: Mode) {
-        let input_1 = self.read(mode_1);
-        let input_2 = self.read(mode_2);
-        let result = input_1 * input_2;
-        self.write(mode_3, result);
+    fn process_2(&mut self, mode_1: Mode, mode_2: Mode, mode_3: Mode) -> Result<(), ErrorCause> {
+        let input_1 = self.read(mode_1)?;
+        let input_2 = self.read(mode_2)?;
+        let result = semantics::evaluate(Opcode::Mul, &input_1, &input_2, self.checked)?;
+        self.write(mode_3, result)
     }
 
-    fn process_3(&mut self, mode: Mode) {
-        if let Some(input) = self.input.pop_front() {
-            self.write(mode, input);
+    fn process_3(&mut self, mode: Mode, io: &mut dyn IntcodeIo) -> Result<(), ErrorCause> {
+        let address = self.write_address(mode)?;
+        if let Some(input) = self.profile_input(io) {
+            self.trace_io(IoEvent::Input(input));
+            self.undo_io(IoEvent::Input(input));
+            self.store(address, input);
         } else {
-            self.pc -= 1;
+            self.pc -= 2;
             self.awaits_input = true;
         }
+        Ok(())
     }
 
-    fn process_4(&mut self, mode: Mode) {
-        let output = self.read(mode);
-        self.output.push_back(output);
+    fn process_4(&mut self, mode: Mode, io: &mut dyn IntcodeIo) -> Result<(), ErrorCause> {
+        let output = self.read(mode)?;
+        self.trace_io(IoEvent::Output(output));
+        self.undo_io(IoEvent::Output(output));
+        io.write(output);
+        Ok(())
     }
 
-    fn process_5(&mut self, mode_1: Mode, mode_2: Mode) {
-        let param_1 = self.read(mode_1);
-        let param_2 = self.read(mode_2);
+    fn process_5(&mut self, mode_1: Mode, mode_2: Mode) -> Result<(), ErrorCause> {
+        let param_1 = self.read(mode_1)?;
+        let param_2 = self.read(mode_2)?;
 
-        if param_1 != 0 {
-            self.pc = param_2 as usize;
+        if semantics::jumps(Opcode::Jnz, &param_1) {
+            self.pc = semantics::to_address(&param_2)?;
         }
+        Ok(())
     }
 
-    fn process_6(&mut self, mode_1: Mode, mode_2: Mode) {
-        let param_1 = self.read(mode_1);
-        let param_2 = self.read(mode_2);
+    fn process_6(&mut self, mode_1: Mode, mode_2: Mode) -> Result<(), ErrorCause> {
+        let param_1 = self.read(mode_1)?;
+        let param_2 = self.read(mode_2)?;
 
-        if param_1 == 0 {
-            self.pc = param_2 as usize;
+        if semantics::jumps(Opcode::Jz, &param_1) {
+            self.pc = semantics::to_address(&param_2)?;
         }
+        Ok(())
     }
 
-    fn process_7(&mut self, mode_1: Mode, mode_2: Mode, mode_3: Mode) {
-        let param_1 = self.read(mode_1);
-        let param_2 = self.read(mode_2);
+    fn process_7(&mut self, mode_1: Mode, mode_2: Mode, mode_3: Mode) -> Result<(), ErrorCause> {
+        let param_1 = self.read(mode_1)?;
+        let param_2 = self.read(mode_2)?;
 
-        if param_1 < param_2 {
-            self.write(mode_3, 1);
-        } else {
-            self.write(mode_3, 0);
-        }
+        let result = semantics::evaluate(Opcode::Lt, &param_1, &param_2, self.checked)?;
+        self.write(mode_3, result)
     }
 
-    fn process_8(&mut self, mode_1: Mode, mode_2: Mode, mode_3: Mode) {
-        let param_1 = self.read(mode_1);
-        let param_2 = self.read(mode_2);
+    fn process_8(&mut self, mode_1: Mode, mode_2: Mode, mode_3: Mode) -> Result<(), ErrorCause> {
+        let param_1 = self.read(mode_1)?;
+        let param_2 = self.read(mode_2)?;
 
-        if param_1 == param_2 {
-            self.write(mode_3, 1);
-        } else {
-            self.write(mode_3, 0);
-        }
+        let result = semantics::evaluate(Opcode::Eq, &param_1, &param_2, self.checked)?;
+        self.write(mode_3, result)
     }
 
-    fn process_9(&mut self, mode: Mode) {
-        let offset = self.read(mode);
-        self.relative_base += offset;
+    fn process_9(&mut self, mode: Mode) -> Result<(), ErrorCause> {
+        let offset = self.read(mode)?;
+        self.relative_base =
+            semantics::relative_address(self.relative_base, &offset, self.checked)?;
+        Ok(())
     }
 
-    fn process_99(&mut self) {
+    fn process_99(&mut self) -> Result<(), ErrorCause> {
         self.finished = true;
+        Ok(())
     }
 
-    pub fn read_from_memory(&mut self, address: usize) -> isize {
-        let entry = self.memory.entry(address).or_insert(0);
-        *entry
+    /// Reports arithmetic overflow as `ErrorCause::Overflow` instead of
+    /// wrapping around.
+    pub fn enable_overflow_checks(&mut self) {
+        self.checked = true;
+    }
+
+    pub fn disable_overflow_checks(&mut self) {
+        self.checked = false;
+    }
+
+    pub fn read_from_memory(&self, address: usize) -> isize {
+        self.memory.get(address)
     }
 
     pub fn write_to_memory(&mut self, address: usize, value: isize) {
-        self.memory.insert(address, value);
+        self.memory.set(address, value);
     }
 
-    fn read(&mut self, mode: Mode) -> isize {
-        match mode {
-            Mode::Position => {
-                let address = self.read_from_memory(self.pc);
-                self.pc += 1;
-                let entry = self.memory.entry(address as usize).or_insert(0);
-                *entry
-            }
-            Mode::Immediate => {
-                let value = self.read_from_memory(self.pc);
-                self.pc += 1;
+    fn read(&mut self, mode: Mode) -> Result<isize, ErrorCause> {
+        let raw = self.read_from_memory(self.pc);
+        self.pc += 1;
 
-                value
-            }
-            Mode::Relative => {
-                let offset = self.read_from_memory(self.pc);
-                self.pc += 1;
-                let entry = self
-                    .memory
-                    .entry((self.relative_base + offset) as usize)
-                    .or_insert(0);
-                *entry
-            }
+        let value = match mode {
+            Mode::Immediate => raw,
+            _ => self.load(semantics::address(
+                mode,
+                &raw,
+                self.relative_base,
+                self.checked,
+            )?),
+        };
+        self.trace_operand(mode, raw, Some(value));
+        Ok(value)
+    }
+
+    fn write_address(&mut self, mode: Mode) -> Result<usize, ErrorCause> {
+        let raw = self.read_from_memory(self.pc);
+        self.pc += 1;
+
+        let address = semantics::address(mode, &raw, self.relative_base, self.checked)?;
+        self.trace_operand(mode, raw, None);
+        Ok(address)
+    }
+
+    fn write(&mut self, mode: Mode, value: isize) -> Result<(), ErrorCause> {
+        let address = self.write_address(mode)?;
+        self.store(address, value);
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.clone(),
+            pc: self.pc,
+            relative_base: self.relative_base,
+            input: self.input.clone(),
+            output: self.output.clone(),
+            finished: self.finished,
+            awaits_input: self.awaits_input,
         }
     }
 
-    fn write(&mut self, mode: Mode, value: isize) {
-        match mode {
-            Mode::Position => {
-                let address = self.read_from_memory(self.pc);
-                self.pc += 1;
-                self.write_to_memory(address as usize, value);
-            }
-            Mode::Immediate => {
-                panic!("write in immediate mode should not happen");
-            }
-            Mode::Relative => {
-                let offset = self.read_from_memory(self.pc);
-                self.pc += 1;
-                self.write_to_memory((self.relative_base + offset) as usize, value);
-            }
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        let mut intcode = Intcode::new(&[]);
+        intcode.restore(snapshot);
+        intcode
+    }
+
+    /// Rewinds the machine to `snapshot`. Watchpoints are left untouched and
+    /// the undo history is cleared.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory = snapshot.memory.clone();
+        self.pc = snapshot.pc;
+        self.relative_base = snapshot.relative_base;
+        self.input = snapshot.input.clone();
+        self.output = snapshot.output.clone();
+        self.finished = snapshot.finished;
+        self.awaits_input = snapshot.awaits_input;
+        self.watch_hits.clear();
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
         }
     }
 
+    fn load(&mut self, address: usize) -> isize {
+        let value = self.read_from_memory(address);
+        self.profile_access(address, None);
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(address, MemoryAccess::Read { value });
+        }
+        value
+    }
+
+    fn store(&mut self, address: usize, value: isize) {
+        self.trace_write(address, value);
+        self.undo_write(address);
+        self.profile_access(address, Some(value));
+        self.self_modification_write(address, value);
+        if !self.watchpoints.is_empty() {
+            let old_value = self.read_from_memory(address);
+            self.check_watchpoints(
+                address,
+                MemoryAccess::Write {
+                    old_value,
+                    new_value: value,
+                },
+            );
+        }
+        self.write_to_memory(address, value);
+    }
+
+    fn check_watchpoints(&mut self, address: usize, access: MemoryAccess) {
+        if self
+            .watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.matches(address, &access))
+        {
+            self.watch_hits.push(WatchHit {
+                pc: self.instruction_pc,
+                address,
+                access,
+            });
+        }
+    }
+
+    /// Stops execution after any instruction that reads or writes (depending
+    /// on `kind`) an address in `range`. Accesses made through
+    /// `read_from_memory`/`write_to_memory` are not reported.
+    pub fn add_watchpoint(&mut self, range: Range<usize>, kind: WatchKind) {
+        self.watchpoints.push(Watchpoint { range, kind });
+    }
+
+    pub fn remove_watchpoint(&mut self, range: Range<usize>, kind: WatchKind) {
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.range != range || watchpoint.kind != kind);
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// All watched accesses made by the most recently executed instruction.
+    pub fn watch_hits(&self) -> &[WatchHit] {
+        &self.watch_hits
+    }
+
     pub fn add_input(&mut self, value: isize) {
         self.awaits_input = false;
         self.input.push_back(value);
     }
 
+    pub fn get_input(&self) -> &VecDeque<isize> {
+        &self.input
+    }
+
     pub fn has_output(&self) -> bool {
         !self.output.is_empty()
     }
@@ -248,4 +512,12 @@ impl Intcode {
     pub fn awaits_input(&self) -> bool {
         self.awaits_input
     }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> isize {
+        self.relative_base
+    }
 }
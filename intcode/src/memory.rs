@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const PAGE_BITS: usize = 10;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+
+/// Addresses below this limit live in pages indexed directly by address;
+/// anything above it is kept in a sparse map so a stray far write does not
+/// allocate gigabytes. Pages of a program longer than the limit stay pages.
+const FLAT_LIMIT: usize = 1 << 20;
+
+/// Intcode memory. Pages are shared between clones and copied on the first
+/// write, so cloning a machine costs one pointer per page. Words are `isize`
+/// unless a wider type is needed, see `word::Machine`.
+#[derive(Clone, Debug, Default)]
+pub struct Memory<T = isize> {
+    pages: Vec<Arc<[T]>>,
+    sparse: Arc<HashMap<usize, T>>,
+}
+
+fn empty_page<T: Clone + Default>() -> Arc<[T]> {
+    vec![T::default(); PAGE_SIZE].into()
+}
+
+impl<T: Clone + Default> Memory<T> {
+    pub fn new(program: &[T]) -> Self {
+        let pages = program
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = vec![T::default(); PAGE_SIZE];
+                page[..chunk.len()].clone_from_slice(chunk);
+                page.into()
+            })
+            .collect();
+
+        Self {
+            pages,
+            sparse: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `address` is stored in a page rather than the sparse map.
+    fn is_paged(&self, address: usize) -> bool {
+        address < FLAT_LIMIT || address >> PAGE_BITS < self.pages.len()
+    }
+
+    pub fn get(&self, address: usize) -> T {
+        match self.pages.get(address >> PAGE_BITS) {
+            Some(page) => page[address & (PAGE_SIZE - 1)].clone(),
+            None if self.is_paged(address) => T::default(),
+            None => self.sparse.get(&address).cloned().unwrap_or_default(),
+        }
+    }
+
+    pub fn set(&mut self, address: usize, value: T) {
+        if self.is_paged(address) {
+            let index = address >> PAGE_BITS;
+            if index >= self.pages.len() {
+                self.pages.resize_with(index + 1, empty_page);
+            }
+            Arc::make_mut(&mut self.pages[index])[address & (PAGE_SIZE - 1)] = value;
+        } else {
+            Arc::make_mut(&mut self.sparse).insert(address, value);
+        }
+    }
+}
+
+impl<T: Clone + Default + PartialEq> Memory<T> {
+    /// Non-zero words in ascending address order.
+    pub fn non_zero(&self) -> Vec<(usize, T)> {
+        let zero = T::default();
+        let mut words: Vec<_> = self
+            .pages
+            .iter()
+            .enumerate()
+            .flat_map(|(index, page)| {
+                page.iter()
+                    .enumerate()
+                    .map(move |(offset, value)| ((index << PAGE_BITS) + offset, value.clone()))
+            })
+            .filter(|(_, value)| *value != zero)
+            .collect();
+
+        let mut sparse: Vec<_> = self
+            .sparse
+            .iter()
+            .filter(|(_, value)| **value != zero)
+            .map(|(address, value)| (*address, value.clone()))
+            .collect();
+        sparse.sort_unstable_by_key(|(address, _)| *address);
+        words.extend(sparse);
+
+        words
+    }
+}
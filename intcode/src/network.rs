@@ -0,0 +1,371 @@
+use crate::{Budget, Intcode, IntcodeError, StepOutcome};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+
+/// Address the default router hands over to the monitor.
+pub const NAT_ADDRESS: isize = 255;
+
+const DEFAULT_TIME_SLICE: u64 = 10_000;
+
+type Hook = Box<dyn FnMut(&NetworkEvent)>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Packet {
+    pub address: isize,
+    pub x: isize,
+    pub y: isize,
+}
+
+impl Packet {
+    pub fn new(address: isize, x: isize, y: isize) -> Self {
+        Packet { address, x, y }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Route {
+    Machine(usize),
+    Monitor,
+    Drop,
+}
+
+pub trait Router {
+    fn route(&mut self, packet: &Packet) -> Route;
+}
+
+impl<F: FnMut(&Packet) -> Route> Router for F {
+    fn route(&mut self, packet: &Packet) -> Route {
+        self(packet)
+    }
+}
+
+/// Delivers packets addressed to one of the machines, passes packets for
+/// `NAT_ADDRESS` to the monitor and drops everything else.
+pub struct AddressRouter {
+    machines: usize,
+}
+
+impl AddressRouter {
+    pub fn new(machines: usize) -> Self {
+        Self { machines }
+    }
+}
+
+impl Router for AddressRouter {
+    fn route(&mut self, packet: &Packet) -> Route {
+        match packet.address {
+            NAT_ADDRESS => Route::Monitor,
+            address if address >= 0 && (address as usize) < self.machines => {
+                Route::Machine(address as usize)
+            }
+            _ => Route::Drop,
+        }
+    }
+}
+
+/// Node that receives packets routed to `Route::Monitor` and may inject a
+/// packet of its own whenever the network goes idle.
+pub trait Monitor {
+    fn receive(&mut self, packet: Packet);
+    fn wake(&mut self) -> Option<Packet>;
+}
+
+/// Keeps the last packet it received and resends it to `target` when the
+/// network is idle.
+pub struct Nat {
+    target: isize,
+    packet: Option<Packet>,
+}
+
+impl Nat {
+    pub fn new(target: isize) -> Self {
+        Self {
+            target,
+            packet: None,
+        }
+    }
+}
+
+impl Monitor for Nat {
+    fn receive(&mut self, packet: Packet) {
+        self.packet = Some(packet);
+    }
+
+    fn wake(&mut self) -> Option<Packet> {
+        self.packet
+            .take()
+            .map(|packet| Packet::new(self.target, packet.x, packet.y))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetworkEvent {
+    /// A machine emitted a packet, before it was routed.
+    Sent {
+        from: usize,
+        packet: Packet,
+    },
+    Dropped(Packet),
+    /// The monitor received a packet.
+    Monitored(Packet),
+    /// The network went idle and the monitor injected a packet.
+    Woken(Packet),
+    /// The network is idle and the monitor has nothing to send.
+    Stalled,
+    Halted(usize),
+}
+
+/// A routed packet, `from` is `None` for packets injected by the host or the
+/// monitor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoggedPacket {
+    pub round: usize,
+    pub from: Option<usize>,
+    pub packet: Packet,
+    pub route: Route,
+}
+
+#[derive(Debug)]
+pub struct NetworkError {
+    pub address: usize,
+    pub error: IntcodeError,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "machine {}: {}", self.address, self.error)
+    }
+}
+
+impl Error for NetworkError {}
+
+struct Machine {
+    intcode: Intcode,
+    /// Set once the machine was told there is no packet for it, cleared by
+    /// any traffic in either direction.
+    polled: bool,
+}
+
+impl Machine {
+    fn is_idle(&self) -> bool {
+        self.intcode.finished()
+            || (self.intcode.awaits_input()
+                && self.intcode.get_input().is_empty()
+                && self.intcode.get_output().is_empty()
+                && self.polled)
+    }
+}
+
+/// Packet switched network of addressable machines, as in day 23.
+///
+/// Every machine boots with its address as the first input and reads `-1`
+/// while no packet is waiting. Machines run in rounds, each for at most one
+/// time slice, and every three outputs form a packet. The network is idle
+/// once every machine is blocked on an empty input queue after having been
+/// told there is nothing for it, which is when the monitor gets to wake it.
+pub struct Network {
+    machines: Vec<Machine>,
+    router: Box<dyn Router>,
+    monitor: Option<Box<dyn Monitor>>,
+    hooks: Vec<Hook>,
+    events: VecDeque<NetworkEvent>,
+    log: Option<Vec<LoggedPacket>>,
+    round: usize,
+    time_slice: u64,
+}
+
+impl Network {
+    /// Boots `size` copies of `program` behind an `AddressRouter`, with a
+    /// `Nat` monitor waking machine 0.
+    pub fn new(program: &[isize], size: usize) -> Self {
+        let machines = (0..size)
+            .map(|address| {
+                let mut intcode = Intcode::new(program);
+                intcode.add_input(address as isize);
+                Machine {
+                    intcode,
+                    polled: false,
+                }
+            })
+            .collect();
+
+        Self {
+            machines,
+            router: Box::new(AddressRouter::new(size)),
+            monitor: Some(Box::new(Nat::new(0))),
+            hooks: Vec::new(),
+            events: VecDeque::new(),
+            log: None,
+            round: 0,
+            time_slice: DEFAULT_TIME_SLICE,
+        }
+    }
+
+    pub fn with_router(self, router: impl Router + 'static) -> Self {
+        Self {
+            router: Box::new(router),
+            ..self
+        }
+    }
+
+    pub fn with_monitor(self, monitor: impl Monitor + 'static) -> Self {
+        Self {
+            monitor: Some(Box::new(monitor)),
+            ..self
+        }
+    }
+
+    pub fn without_monitor(self) -> Self {
+        Self {
+            monitor: None,
+            ..self
+        }
+    }
+
+    /// Sets how many instructions a machine may execute per round.
+    pub fn with_time_slice(self, time_slice: u64) -> Self {
+        Self { time_slice, ..self }
+    }
+
+    /// Calls `hook` for every event, in addition to returning it from
+    /// `next_event`.
+    pub fn on_event(&mut self, hook: impl FnMut(&NetworkEvent) + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Starts recording every routed packet.
+    pub fn enable_log(&mut self) {
+        self.log.get_or_insert_with(Vec::new);
+    }
+
+    pub fn log(&self) -> &[LoggedPacket] {
+        self.log.as_deref().unwrap_or(&[])
+    }
+
+    pub fn size(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn machine(&self, address: usize) -> &Intcode {
+        &self.machines[address].intcode
+    }
+
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.machines.iter().all(Machine::is_idle)
+    }
+
+    /// Routes a packet as if a machine had sent it.
+    pub fn send(&mut self, packet: Packet) {
+        self.route(None, packet);
+    }
+
+    /// Runs rounds until something happens.
+    pub fn next_event(&mut self) -> Result<NetworkEvent, NetworkError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            self.run_round()?;
+        }
+    }
+
+    fn run_round(&mut self) -> Result<(), NetworkError> {
+        self.round += 1;
+
+        for address in 0..self.machines.len() {
+            let machine = &mut self.machines[address];
+            if machine.intcode.finished() {
+                continue;
+            }
+            if machine.intcode.awaits_input() && machine.intcode.get_input().is_empty() {
+                machine.intcode.add_input(-1);
+                machine.polled = true;
+            }
+
+            let outcome = machine
+                .intcode
+                .run_with_budget(Budget::instructions(self.time_slice))
+                .map_err(|error| NetworkError { address, error })?;
+
+            let mut packets = Vec::new();
+            while machine.intcode.get_output().len() >= 3 {
+                let address = machine.intcode.get_first_output().unwrap();
+                let x = machine.intcode.get_first_output().unwrap();
+                let y = machine.intcode.get_first_output().unwrap();
+                packets.push(Packet::new(address, x, y));
+                machine.polled = false;
+            }
+            if machine.intcode.has_output() {
+                machine.polled = false;
+            }
+
+            for packet in packets {
+                self.emit(NetworkEvent::Sent {
+                    from: address,
+                    packet,
+                });
+                self.route(Some(address), packet);
+            }
+            if outcome == StepOutcome::Finished {
+                self.emit(NetworkEvent::Halted(address));
+            }
+        }
+
+        if self.is_idle() {
+            match self.monitor.as_mut().and_then(|monitor| monitor.wake()) {
+                Some(packet) => {
+                    self.emit(NetworkEvent::Woken(packet));
+                    self.route(None, packet);
+                }
+                None => self.emit(NetworkEvent::Stalled),
+            }
+        }
+        Ok(())
+    }
+
+    /// Delivers `packet` where the router says. A route to a machine the
+    /// network does not have drops the packet and is logged as `Route::Drop`.
+    fn route(&mut self, from: Option<usize>, packet: Packet) {
+        let route = match self.router.route(&packet) {
+            Route::Machine(address) if address >= self.machines.len() => Route::Drop,
+            route => route,
+        };
+        if let Some(log) = self.log.as_mut() {
+            log.push(LoggedPacket {
+                round: self.round,
+                from,
+                packet,
+                route,
+            });
+        }
+
+        match route {
+            Route::Machine(address) => {
+                let machine = &mut self.machines[address];
+                machine.intcode.add_input(packet.x);
+                machine.intcode.add_input(packet.y);
+                machine.polled = false;
+            }
+            Route::Monitor => match self.monitor.as_mut() {
+                Some(monitor) => {
+                    monitor.receive(packet);
+                    self.emit(NetworkEvent::Monitored(packet));
+                }
+                None => self.emit(NetworkEvent::Dropped(packet)),
+            },
+            Route::Drop => self.emit(NetworkEvent::Dropped(packet)),
+        }
+    }
+
+    fn emit(&mut self, event: NetworkEvent) {
+        for hook in self.hooks.iter_mut() {
+            hook(&event);
+        }
+        self.events.push_back(event);
+    }
+}
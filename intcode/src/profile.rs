@@ -0,0 +1,223 @@
+use crate::instruction::Opcode;
+use crate::io::IntcodeIo;
+use crate::Intcode;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccessCount {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+/// Execution statistics gathered by `Intcode::start_profile`.
+///
+/// Functions are inferred while running: a taken jump right after the
+/// previous instruction stored the jump's return address at `[rb+0]` enters
+/// a function, a taken jump through `[rb+0]` leaves it.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub instructions: u64,
+    pub pc_counts: HashMap<usize, u64>,
+    pub opcode_counts: HashMap<isize, u64>,
+    pub memory: HashMap<usize, AccessCount>,
+    /// Time spent blocked on input, either inside `IntcodeIo::read` or
+    /// between the machine asking for input and the host providing it.
+    pub input_wait: Duration,
+    /// Executed instructions per call stack of function entry points.
+    pub stacks: HashMap<Vec<usize>, u64>,
+    stack: Vec<usize>,
+    store: Option<(usize, isize)>,
+    previous_store: Option<(usize, isize)>,
+    waiting_since: Option<Instant>,
+}
+
+fn function_name(entry: usize) -> String {
+    match entry {
+        0 => String::from("main"),
+        _ => format!("fn_{:04}", entry),
+    }
+}
+
+fn sorted<K: Copy + Ord>(counts: &HashMap<K, u64>) -> Vec<(K, u64)> {
+    let mut counts: Vec<_> = counts.iter().map(|(key, count)| (*key, *count)).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+}
+
+impl Profile {
+    /// Hot spots sorted by execution count, at most `limit` per table.
+    pub fn report(&self, limit: usize) -> String {
+        let percent = |count: u64| 100.0 * count as f64 / self.instructions.max(1) as f64;
+        let mut report = String::new();
+
+        writeln!(report, "instructions: {}", self.instructions).unwrap();
+        writeln!(report, "input wait:   {:?}", self.input_wait).unwrap();
+
+        writeln!(report, "\nopcodes:").unwrap();
+        for (opcode, count) in sorted(&self.opcode_counts) {
+            let name = Opcode::try_from(opcode)
+                .map(|opcode| opcode.mnemonic().to_uppercase())
+                .unwrap_or_else(|_| opcode.to_string());
+            writeln!(
+                report,
+                "  {:<4} {:>12} {:>6.2}%",
+                name,
+                count,
+                percent(count)
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nhot pcs:").unwrap();
+        for (pc, count) in sorted(&self.pc_counts).into_iter().take(limit) {
+            writeln!(report, "  {:04} {:>12} {:>6.2}%", pc, count, percent(count)).unwrap();
+        }
+
+        let mut functions = HashMap::new();
+        for (stack, count) in &self.stacks {
+            *functions
+                .entry(stack.last().copied().unwrap_or(0))
+                .or_insert(0) += count;
+        }
+        writeln!(report, "\nfunctions (self):").unwrap();
+        for (entry, count) in sorted(&functions).into_iter().take(limit) {
+            writeln!(
+                report,
+                "  {:<8} {:>12} {:>6.2}%",
+                function_name(entry),
+                count,
+                percent(count)
+            )
+            .unwrap();
+        }
+
+        let accesses: HashMap<_, _> = self
+            .memory
+            .iter()
+            .map(|(address, count)| (*address, count.reads + count.writes))
+            .collect();
+        writeln!(report, "\nmemory:").unwrap();
+        for (address, _) in sorted(&accesses).into_iter().take(limit) {
+            let count = self.memory[&address];
+            writeln!(
+                report,
+                "  [{}] {} reads, {} writes",
+                address, count.reads, count.writes
+            )
+            .unwrap();
+        }
+
+        report
+    }
+
+    /// Call stacks in the folded format read by flamegraph tools, one
+    /// `main;fn_0303;fn_0225 count` line per stack.
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<_> = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| {
+                let frames: Vec<_> = Some(0)
+                    .into_iter()
+                    .chain(stack.iter().copied())
+                    .map(function_name)
+                    .collect();
+                format!("{} {}", frames.join(";"), count)
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.report(20))
+    }
+}
+
+impl Intcode {
+    /// Starts collecting a `Profile`, discarding any previous one.
+    pub fn start_profile(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    pub(crate) fn profile_access(&mut self, address: usize, value: Option<isize>) {
+        if let Some(profile) = self.profile.as_mut() {
+            let count = profile.memory.entry(address).or_default();
+            match value {
+                None => count.reads += 1,
+                Some(value) => {
+                    count.writes += 1;
+                    profile.store = Some((address, value));
+                }
+            }
+        }
+    }
+
+    pub(crate) fn profile_input(&mut self, io: &mut dyn IntcodeIo) -> Option<isize> {
+        let profile = match self.profile.as_mut() {
+            Some(profile) => profile,
+            None => return io.read(),
+        };
+
+        let started = Instant::now();
+        let input = io.read();
+        profile.input_wait += started.elapsed();
+        if input.is_some() {
+            if let Some(since) = profile.waiting_since.take() {
+                profile.input_wait += since.elapsed();
+            }
+        }
+        input
+    }
+
+    /// Accounts for the instruction at `pc` once it completed.
+    pub(crate) fn profile_step(&mut self, pc: usize, instruction: isize) {
+        if self.profile.is_none() {
+            return;
+        }
+        let relative_base = self.relative_base;
+        let return_pointer = self.read_from_memory(pc + 2);
+        let profile = self.profile.as_mut().unwrap();
+
+        let store = profile.store.take();
+        if self.awaits_input {
+            profile.waiting_since.get_or_insert_with(Instant::now);
+            return;
+        }
+
+        let opcode = instruction % 100;
+        profile.instructions += 1;
+        *profile.pc_counts.entry(pc).or_insert(0) += 1;
+        *profile.opcode_counts.entry(opcode).or_insert(0) += 1;
+        match profile.stacks.get_mut(&profile.stack) {
+            Some(count) => *count += 1,
+            None => {
+                profile.stacks.insert(profile.stack.clone(), 1);
+            }
+        }
+
+        let jumped = (opcode == 5 || opcode == 6) && self.pc != pc + 3;
+        if jumped && instruction / 1000 % 10 == 2 && return_pointer == 0 {
+            profile.stack.pop();
+        } else if jumped
+            && relative_base >= 0
+            && profile.previous_store == Some((relative_base as usize, pc as isize + 3))
+        {
+            profile.stack.push(self.pc);
+        }
+        profile.previous_store = store;
+    }
+}
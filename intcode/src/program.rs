@@ -0,0 +1,364 @@
+//! Loading intcode programs from text.
+//!
+//! Values are separated by commas, with any whitespace and line breaks around
+//! them and an optional trailing comma. `#` starts a comment that runs to the
+//! end of the line. Comment lines before the first value may form a header of
+//! `# key: value` lines:
+//!
+//! ```text
+//! # name: BOOST
+//! # io: numeric
+//! 1102,34463338,34463338,63,
+//! 1007,63,34463338,63,
+//! ...
+//! ```
+//!
+//! `name` is free text and `io` is `numeric` or `ascii`. Other keys are
+//! ignored like any comment.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How a program expects to talk to its host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// Plain numbers in and out.
+    Numeric,
+    /// Text, one character code per value, see `Intcode::send_line`.
+    Ascii,
+}
+
+impl FromStr for Protocol {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "numeric" => Ok(Protocol::Numeric),
+            "ascii" => Ok(Protocol::Ascii),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    InvalidNumber(String),
+    /// A comma with no value in front of it.
+    MissingValue,
+    /// Two values with only whitespace between them.
+    MissingComma,
+    UnknownProtocol(String),
+    Empty,
+}
+
+/// Where a program failed to parse, with lines and columns counted from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::InvalidNumber(value) => write!(f, "invalid number `{}`", value),
+            ParseErrorKind::MissingValue => write!(f, "missing value before `,`"),
+            ParseErrorKind::MissingComma => write!(f, "expected `,` between values"),
+            ParseErrorKind::UnknownProtocol(protocol) => {
+                write!(f, "unknown io protocol `{}`", protocol)
+            }
+            ParseErrorKind::Empty => write!(f, "program has no values"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.kind
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "{}", error),
+            LoadError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(error: ParseError) -> Self {
+        LoadError::Parse(error)
+    }
+}
+
+/// An intcode program with its header. Derefs to the program's words, so it
+/// can be passed wherever a `&[isize]` is expected, `Intcode::new` included.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Program {
+    pub name: Option<String>,
+    pub protocol: Option<Protocol>,
+    words: Vec<isize>,
+}
+
+impl Program {
+    pub fn new(words: Vec<isize>) -> Self {
+        Self {
+            name: None,
+            protocol: None,
+            words,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let source = fs::read_to_string(path)?;
+        Ok(Self::parse(&source)?)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut program = Self::default();
+        let mut expects_value = true;
+        let mut last_line = 1;
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            last_line = line_number;
+            let (code, comment) = match line.find('#') {
+                Some(start) => (&line[..start], Some(start + 1)),
+                None => (line, None),
+            };
+
+            let mut tokens = code.char_indices().peekable();
+            while let Some((start, character)) = tokens.next() {
+                let error = |kind| ParseError {
+                    line: line_number,
+                    column: column(line, start),
+                    kind,
+                };
+                if character.is_whitespace() {
+                    continue;
+                }
+                if character == ',' {
+                    if expects_value {
+                        return Err(error(ParseErrorKind::MissingValue));
+                    }
+                    expects_value = true;
+                    continue;
+                }
+
+                let mut end = start + character.len_utf8();
+                while let Some((next, character)) = tokens.peek() {
+                    if character.is_whitespace() || *character == ',' {
+                        break;
+                    }
+                    end = next + character.len_utf8();
+                    tokens.next();
+                }
+                if !expects_value {
+                    return Err(error(ParseErrorKind::MissingComma));
+                }
+                let token = &code[start..end];
+                let value = token
+                    .parse()
+                    .map_err(|_| error(ParseErrorKind::InvalidNumber(token.to_string())))?;
+                program.words.push(value);
+                expects_value = false;
+            }
+
+            if let (Some(start), true) = (comment, program.words.is_empty()) {
+                program.header(line, line_number, start)?;
+            }
+        }
+
+        if program.words.is_empty() {
+            return Err(ParseError {
+                line: last_line,
+                column: 1,
+                kind: ParseErrorKind::Empty,
+            });
+        }
+        Ok(program)
+    }
+
+    /// Reads a `key: value` header entry from the comment starting at byte
+    /// `start` of `line`.
+    fn header(&mut self, line: &str, line_number: usize, start: usize) -> Result<(), ParseError> {
+        let comment = &line[start..];
+        let separator = match comment.find(':') {
+            Some(separator) => separator,
+            None => return Ok(()),
+        };
+        let value = comment[separator + 1..].trim();
+
+        match comment[..separator].trim() {
+            "name" => self.name = Some(value.to_string()),
+            "io" => {
+                let protocol = value.parse().map_err(|_| {
+                    let offset = start + separator + 1;
+                    let leading = line[offset..].len() - line[offset..].trim_start().len();
+                    ParseError {
+                        line: line_number,
+                        column: column(line, offset + leading),
+                        kind: ParseErrorKind::UnknownProtocol(value.to_string()),
+                    }
+                })?;
+                self.protocol = Some(protocol);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn words(&self) -> &[isize] {
+        &self.words
+    }
+
+    pub fn into_words(self) -> Vec<isize> {
+        self.words
+    }
+}
+
+/// Column, counted in characters from 1, of byte `offset` in `line`.
+fn column(line: &str, offset: usize) -> usize {
+    line[..offset].chars().count() + 1
+}
+
+impl Deref for Program {
+    type Target = [isize];
+
+    fn deref(&self) -> &[isize] {
+        &self.words
+    }
+}
+
+impl From<Vec<isize>> for Program {
+    fn from(words: Vec<isize>) -> Self {
+        Self::new(words)
+    }
+}
+
+impl FromStr for Program {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> (usize, usize, ParseErrorKind) {
+        let error = Program::parse(source).unwrap_err();
+        (error.line, error.column, error.kind)
+    }
+
+    #[test]
+    fn reads_values_across_lines() {
+        let program = Program::parse("1, 2,\n  -3 ,4\n").unwrap();
+        assert_eq!(program.words(), [1, 2, -3, 4]);
+        assert_eq!(program.name, None);
+        assert_eq!(program.protocol, None);
+    }
+
+    #[test]
+    fn accepts_a_trailing_comma() {
+        let program = Program::parse("1,2,99,\n").unwrap();
+        assert_eq!(program.words(), [1, 2, 99]);
+    }
+
+    #[test]
+    fn skips_comments() {
+        let program = Program::parse("# add\n1101,1,1,0, # sum\n# halt\n99 # done").unwrap();
+        assert_eq!(program.words(), [1101, 1, 1, 0, 99]);
+    }
+
+    #[test]
+    fn reads_the_header() {
+        let program = Program::parse(
+            "# name: Sensor boost
+             #io:   ascii
+             # author: someone
+             104,10,99
+             # io: numeric",
+        )
+        .unwrap();
+        assert_eq!(program.name.as_deref(), Some("Sensor boost"));
+        assert_eq!(program.protocol, Some(Protocol::Ascii));
+        assert_eq!(program.words(), [104, 10, 99]);
+    }
+
+    #[test]
+    fn reports_a_missing_value() {
+        assert_eq!(error(",1"), (1, 1, ParseErrorKind::MissingValue));
+        assert_eq!(error("1,\n 2,, 3"), (2, 4, ParseErrorKind::MissingValue));
+    }
+
+    #[test]
+    fn reports_a_missing_comma() {
+        assert_eq!(error("1, 2\n3"), (2, 1, ParseErrorKind::MissingComma));
+        assert_eq!(error("1,2  99"), (1, 6, ParseErrorKind::MissingComma));
+    }
+
+    #[test]
+    fn reports_an_invalid_number() {
+        assert_eq!(
+            error("1,\n2, x3,4"),
+            (2, 4, ParseErrorKind::InvalidNumber("x3".to_string()))
+        );
+        assert_eq!(
+            error("1,2.5"),
+            (1, 3, ParseErrorKind::InvalidNumber("2.5".to_string()))
+        );
+    }
+
+    #[test]
+    fn reports_an_unknown_protocol() {
+        assert_eq!(
+            error("# name: x\n# io:  binary\n99"),
+            (2, 8, ParseErrorKind::UnknownProtocol("binary".to_string()))
+        );
+    }
+
+    #[test]
+    fn reports_an_empty_program() {
+        assert_eq!(error("# io: ascii\n\n"), (2, 1, ParseErrorKind::Empty));
+    }
+
+    #[test]
+    fn displays_the_position() {
+        let error = Program::parse("1 2").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 1, column 3: expected `,` between values"
+        );
+    }
+}
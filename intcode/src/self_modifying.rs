@@ -0,0 +1,143 @@
+use crate::instruction::Opcode;
+use crate::Intcode;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// What the machine does when a program overwrites code it already ran.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnCodeWrite {
+    /// Keep running, only record the write.
+    Record,
+    /// Record the write and stop with `StepOutcome::CodeWrite` after the
+    /// instruction that made it. This takes precedence over a watchpoint hit
+    /// by the same instruction, which is still listed in `watch_hits`.
+    Halt,
+}
+
+/// A write into a word of a previously executed instruction, made by the
+/// instruction at `pc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CodeWrite {
+    pub pc: usize,
+    pub address: usize,
+    pub old_value: isize,
+    pub new_value: isize,
+}
+
+impl fmt::Display for CodeWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04} wrote {} to code at [{}] (was {})",
+            self.pc, self.new_value, self.address, self.old_value
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SelfModification {
+    on_write: OnCodeWrite,
+    /// Every word of every instruction executed so far.
+    executed: HashSet<usize>,
+    /// Words of the instruction being executed, added to `executed` once it
+    /// completes.
+    current: Option<RangeInclusive<usize>>,
+    writes: Vec<CodeWrite>,
+    hit: Option<CodeWrite>,
+}
+
+impl Intcode {
+    /// Starts watching for writes into addresses that were executed as part of
+    /// an instruction, the current one included. Writes made through
+    /// `write_to_memory` are not checked.
+    pub fn enable_self_modification_checks(&mut self, on_write: OnCodeWrite) {
+        self.self_modification = Some(SelfModification {
+            on_write,
+            executed: HashSet::new(),
+            current: None,
+            writes: Vec::new(),
+            hit: None,
+        });
+    }
+
+    pub fn disable_self_modification_checks(&mut self) {
+        self.self_modification = None;
+    }
+
+    /// Code writes recorded since the checks were enabled.
+    pub fn code_writes(&self) -> &[CodeWrite] {
+        self.self_modification
+            .as_ref()
+            .map_or(&[], |checks| &checks.writes)
+    }
+
+    pub fn take_code_writes(&mut self) -> Vec<CodeWrite> {
+        self.self_modification
+            .as_mut()
+            .map(|checks| checks.writes.split_off(0))
+            .unwrap_or_default()
+    }
+
+    /// Treats the words of the instruction at `pc` as code while it runs.
+    pub(crate) fn self_modification_step(&mut self, pc: usize, opcode: isize) {
+        if self.self_modification.is_none() {
+            return;
+        }
+        let parameters = match Opcode::try_from(opcode) {
+            Ok(opcode) => opcode.parameters(),
+            Err(_) => self
+                .instruction_set
+                .as_ref()
+                .and_then(|set| set.custom(opcode))
+                .map_or(0, |custom| custom.parameters.len()),
+        };
+
+        let checks = self.self_modification.as_mut().unwrap();
+        checks.hit = None;
+        checks.current = Some(pc..=pc + parameters);
+    }
+
+    /// Marks the current instruction as executed, once it ran to completion.
+    pub(crate) fn self_modification_executed(&mut self) {
+        if let Some(checks) = self.self_modification.as_mut() {
+            checks
+                .executed
+                .extend(checks.current.take().into_iter().flatten());
+        }
+    }
+
+    pub(crate) fn self_modification_write(&mut self, address: usize, value: isize) {
+        if self.self_modification.is_none() {
+            return;
+        }
+        let old_value = self.read_from_memory(address);
+        let pc = self.instruction_pc;
+        if let Some(checks) = self.self_modification.as_mut() {
+            let current = checks
+                .current
+                .as_ref()
+                .is_some_and(|words| words.contains(&address));
+            if current || checks.executed.contains(&address) {
+                let write = CodeWrite {
+                    pc,
+                    address,
+                    old_value,
+                    new_value: value,
+                };
+                checks.writes.push(write);
+                checks.hit.get_or_insert(write);
+            }
+        }
+    }
+
+    /// The first code write of the last instruction, if the checks halt on it.
+    pub(crate) fn self_modification_hit(&mut self) -> Option<CodeWrite> {
+        let checks = self.self_modification.as_mut()?;
+        match checks.on_write {
+            OnCodeWrite::Halt => checks.hit.take(),
+            OnCodeWrite::Record => None,
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Instruction rules shared by `Intcode`, `CachedIntcode` and `word::Machine`.
+//! The machines differ in how they fetch, store and hook into execution, but
+//! decode, compute and resolve addresses through these functions.
+
+use crate::instruction::{self, Mode, Opcode};
+use crate::word::Word;
+use crate::ErrorCause;
+
+/// Splits an instruction word into its opcode and parameter modes.
+pub(crate) fn decode<W: Word>(word: &W) -> Result<(isize, Mode, Mode, Mode), ErrorCause> {
+    let value = word.to_isize().ok_or(ErrorCause::InstructionOutOfRange)?;
+    instruction::decode(value)
+}
+
+pub(crate) fn to_address<W: Word>(value: &W) -> Result<usize, ErrorCause> {
+    let address = value.to_isize().ok_or(ErrorCause::AddressOutOfRange)?;
+    if address < 0 {
+        Err(ErrorCause::NegativeAddress(address))
+    } else {
+        Ok(address as usize)
+    }
+}
+
+/// Result of `add`, `mul`, `lt` or `eq`. With `checked` an overflowing
+/// result is an error, otherwise it wraps.
+pub(crate) fn evaluate<W: Word>(
+    opcode: Opcode,
+    a: &W,
+    b: &W,
+    checked: bool,
+) -> Result<W, ErrorCause> {
+    let (result, wrapped) = match opcode {
+        Opcode::Add => (a.checked_add(b), a.wrapping_add(b)),
+        Opcode::Mul => (a.checked_mul(b), a.wrapping_mul(b)),
+        Opcode::Lt => return Ok(W::from_isize((a < b) as isize)),
+        Opcode::Eq => return Ok(W::from_isize((a == b) as isize)),
+        _ => unreachable!("{:?} computes no value", opcode),
+    };
+    match result {
+        Some(result) => Ok(result),
+        None if checked => Err(ErrorCause::Overflow),
+        None => Ok(wrapped),
+    }
+}
+
+/// Whether `jnz` or `jz` jumps on `condition`.
+pub(crate) fn jumps<W: Word>(opcode: Opcode, condition: &W) -> bool {
+    (*condition != W::default()) == (opcode == Opcode::Jnz)
+}
+
+/// `relative_base + offset`, checked like arithmetic.
+pub(crate) fn relative_address<W: Word>(
+    relative_base: isize,
+    offset: &W,
+    checked: bool,
+) -> Result<isize, ErrorCause> {
+    let offset = offset.to_isize().ok_or(ErrorCause::AddressOutOfRange)?;
+    match relative_base.checked_add(offset) {
+        Some(address) => Ok(address),
+        None if checked => Err(ErrorCause::Overflow),
+        None => Ok(relative_base.wrapping_add(offset)),
+    }
+}
+
+/// The address a position or relative parameter `raw` refers to.
+pub(crate) fn address<W: Word>(
+    mode: Mode,
+    raw: &W,
+    relative_base: isize,
+    checked: bool,
+) -> Result<usize, ErrorCause> {
+    match mode {
+        Mode::Position => to_address(raw),
+        Mode::Immediate => Err(ErrorCause::WriteInImmediateMode),
+        Mode::Relative => to_address(&relative_address(relative_base, raw, checked)?),
+    }
+}
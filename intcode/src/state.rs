@@ -0,0 +1,382 @@
+use crate::memory::Memory;
+use crate::{Intcode, Snapshot};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::encoding::{write_signed, write_unsigned, write_values, DecodeError, Reader};
+
+const VERSION: u64 = 1;
+const TEXT_HEADER: &str = "intcode-state";
+const BINARY_MAGIC: &[u8] = b"ICST";
+
+/// Zero runs shorter than this are stored inline instead of starting a new
+/// memory segment.
+const MAX_GAP: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateFormat {
+    Text,
+    Binary,
+}
+
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    UnknownFormat,
+    UnsupportedVersion(u64),
+    Malformed(String),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::Io(error) => write!(f, "{}", error),
+            StateError::UnknownFormat => write!(f, "not an intcode state file"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported state version {}", version)
+            }
+            StateError::Malformed(message) => write!(f, "malformed state: {}", message),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+impl From<DecodeError> for StateError {
+    fn from(error: DecodeError) -> Self {
+        StateError::Malformed(error.0.to_string())
+    }
+}
+
+impl From<io::Error> for StateError {
+    fn from(error: io::Error) -> Self {
+        StateError::Io(error)
+    }
+}
+
+fn malformed<T>(message: &str) -> Result<T, StateError> {
+    Err(StateError::Malformed(message.to_string()))
+}
+
+/// Groups non-zero words into `(start, values)` segments.
+fn segments(memory: &Memory) -> Vec<(usize, Vec<isize>)> {
+    let mut segments: Vec<(usize, Vec<isize>)> = Vec::new();
+    for (address, value) in memory.non_zero() {
+        match segments.last_mut() {
+            Some((start, values)) if address - (*start + values.len()) < MAX_GAP => {
+                values.resize(address - *start, 0);
+                values.push(value);
+            }
+            _ => segments.push((address, vec![value])),
+        }
+    }
+    segments
+}
+
+fn join<'a>(values: impl Iterator<Item = &'a isize>) -> String {
+    let values: Vec<_> = values.map(|v| v.to_string()).collect();
+    values.join(",")
+}
+
+fn split(text: &str) -> Result<Vec<isize>, StateError> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    text.split(',')
+        .map(|value| {
+            value
+                .trim()
+                .parse::<isize>()
+                .map_err(|_| StateError::Malformed(format!("invalid value `{}`", value)))
+        })
+        .collect()
+}
+
+/// Address of the word `offset` places after `start`, which a corrupt file
+/// can push past the end of the address space.
+fn memory_address(start: usize, offset: usize) -> Result<usize, StateError> {
+    match start.checked_add(offset) {
+        Some(address) => Ok(address),
+        None => malformed(&format!("memory segment at {} is out of range", start)),
+    }
+}
+
+fn parse_flag(value: &str) -> Result<bool, StateError> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => malformed(&format!("invalid flag `{}`", value)),
+    }
+}
+
+impl Snapshot {
+    /// Writes the state as `key value` lines, with lists comma separated like
+    /// the puzzle inputs.
+    pub fn write_text(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "{} {}", TEXT_HEADER, VERSION)?;
+        writeln!(writer, "pc {}", self.pc)?;
+        writeln!(writer, "relative-base {}", self.relative_base)?;
+        writeln!(writer, "finished {}", self.finished)?;
+        writeln!(writer, "awaits-input {}", self.awaits_input)?;
+        writeln!(writer, "input {}", join(self.input.iter()))?;
+        writeln!(writer, "output {}", join(self.output.iter()))?;
+        for (start, values) in segments(&self.memory) {
+            writeln!(writer, "memory {} {}", start, join(values.iter()))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_binary(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(BINARY_MAGIC)?;
+        write_unsigned(writer, VERSION)?;
+        write_unsigned(writer, self.pc as u64)?;
+        write_signed(writer, self.relative_base)?;
+        write_unsigned(
+            writer,
+            self.finished as u64 | (self.awaits_input as u64) << 1,
+        )?;
+        write_values(writer, self.input.iter())?;
+        write_values(writer, self.output.iter())?;
+
+        let segments = segments(&self.memory);
+        write_unsigned(writer, segments.len() as u64)?;
+        for (start, values) in segments {
+            write_unsigned(writer, start as u64)?;
+            write_values(writer, values.iter())?;
+        }
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut impl Write, format: StateFormat) -> io::Result<()> {
+        match format {
+            StateFormat::Text => self.write_text(writer),
+            StateFormat::Binary => self.write_binary(writer),
+        }
+    }
+
+    /// Parses a state written by `write`, detecting the format from its header.
+    pub fn parse(bytes: &[u8]) -> Result<Snapshot, StateError> {
+        if bytes.starts_with(BINARY_MAGIC) {
+            Self::parse_binary(&bytes[BINARY_MAGIC.len()..])
+        } else if bytes.starts_with(TEXT_HEADER.as_bytes()) {
+            match std::str::from_utf8(bytes) {
+                Ok(text) => Self::parse_text(text),
+                Err(_) => malformed("text state is not valid UTF-8"),
+            }
+        } else {
+            Err(StateError::UnknownFormat)
+        }
+    }
+
+    fn parse_text(text: &str) -> Result<Snapshot, StateError> {
+        let mut lines = text.lines();
+        let version = lines
+            .next()
+            .and_then(|header| header.strip_prefix(TEXT_HEADER))
+            .and_then(|version| version.trim().parse::<u64>().ok());
+        match version {
+            Some(VERSION) => (),
+            Some(version) => return Err(StateError::UnsupportedVersion(version)),
+            None => return malformed("invalid header"),
+        }
+
+        let mut snapshot = Snapshot::empty();
+        for line in lines.map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, value) = match line.find(' ') {
+                Some(space) => (&line[..space], line[space + 1..].trim()),
+                None => (line, ""),
+            };
+            let number = || {
+                value
+                    .parse::<isize>()
+                    .map_err(|_| StateError::Malformed(format!("invalid {} `{}`", key, value)))
+            };
+
+            match key {
+                "pc" => {
+                    snapshot.pc = match value.parse::<usize>() {
+                        Ok(pc) => pc,
+                        Err(_) => return malformed(&format!("invalid pc `{}`", value)),
+                    }
+                }
+                "relative-base" => snapshot.relative_base = number()?,
+                "finished" => snapshot.finished = parse_flag(value)?,
+                "awaits-input" => snapshot.awaits_input = parse_flag(value)?,
+                "input" => snapshot.input = split(value)?.into(),
+                "output" => snapshot.output = split(value)?.into(),
+                "memory" => {
+                    let (start, values) = match value.find(' ') {
+                        Some(space) => (&value[..space], &value[space + 1..]),
+                        None => return malformed("memory line without values"),
+                    };
+                    let start = match start.parse::<usize>() {
+                        Ok(start) => start,
+                        Err(_) => return malformed(&format!("invalid address `{}`", start)),
+                    };
+                    for (offset, value) in split(values)?.into_iter().enumerate() {
+                        snapshot.memory.set(memory_address(start, offset)?, value);
+                    }
+                }
+                _ => return malformed(&format!("unknown key `{}`", key)),
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    fn parse_binary(bytes: &[u8]) -> Result<Snapshot, StateError> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.unsigned()?;
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let mut snapshot = Snapshot::empty();
+        snapshot.pc = reader.usize()?;
+        snapshot.relative_base = reader.signed()?;
+        let flags = reader.unsigned()?;
+        snapshot.finished = flags & 1 != 0;
+        snapshot.awaits_input = flags & 2 != 0;
+        snapshot.input = reader.values()?.into();
+        snapshot.output = reader.values()?.into();
+
+        let segments = reader.usize()?;
+        for _ in 0..segments {
+            let start = reader.usize()?;
+            for (offset, value) in reader.values()?.into_iter().enumerate() {
+                snapshot.memory.set(memory_address(start, offset)?, value);
+            }
+        }
+
+        if !reader.is_empty() {
+            return malformed("trailing data");
+        }
+        Ok(snapshot)
+    }
+
+    fn empty() -> Self {
+        Snapshot {
+            memory: Memory::new(&[]),
+            pc: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            finished: false,
+            awaits_input: false,
+        }
+    }
+}
+
+impl Intcode {
+    pub fn save_state(
+        &self,
+        path: impl AsRef<Path>,
+        format: StateFormat,
+    ) -> Result<(), StateError> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        self.snapshot().write(&mut file, format)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn load_state(path: impl AsRef<Path>) -> Result<Intcode, StateError> {
+        let bytes = fs::read(path)?;
+        Ok(Intcode::from_snapshot(&Snapshot::parse(&bytes)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine() -> Intcode {
+        let mut intcode = Intcode::new(&[3, 20, 4, 20, 109, -7, 3, 1000, 99]);
+        intcode.add_input(42);
+        intcode.add_input(-5);
+        intcode.execute_single_instruction().unwrap();
+        intcode.execute_single_instruction().unwrap();
+        intcode.execute_single_instruction().unwrap();
+        intcode.write_to_memory(5_000_000, 17);
+        intcode
+    }
+
+    fn assert_same(expected: &Snapshot, actual: &Snapshot) {
+        assert_eq!(actual.pc, expected.pc);
+        assert_eq!(actual.relative_base, expected.relative_base);
+        assert_eq!(actual.input, expected.input);
+        assert_eq!(actual.output, expected.output);
+        assert_eq!(actual.finished, expected.finished);
+        assert_eq!(actual.awaits_input, expected.awaits_input);
+        assert_eq!(actual.memory.non_zero(), expected.memory.non_zero());
+    }
+
+    fn round_trip(format: StateFormat) {
+        let expected = machine().snapshot();
+        let mut bytes = Vec::new();
+        expected.write(&mut bytes, format).unwrap();
+        assert_same(&expected, &Snapshot::parse(&bytes).unwrap());
+    }
+
+    #[test]
+    fn text_round_trip() {
+        round_trip(StateFormat::Text);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        round_trip(StateFormat::Binary);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let intcode = machine();
+        for (format, name) in [
+            (StateFormat::Text, "intcode-state-test.txt"),
+            (StateFormat::Binary, "intcode-state-test.bin"),
+        ]
+        .iter()
+        {
+            let path = std::env::temp_dir().join(name);
+            intcode.save_state(&path, *format).unwrap();
+            let mut loaded = Intcode::load_state(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            assert_same(&intcode.snapshot(), &loaded.snapshot());
+            assert_eq!(loaded.run().unwrap(), crate::StepOutcome::Finished);
+            assert_eq!(loaded.get_output(), &[42]);
+        }
+    }
+
+    #[test]
+    fn negative_pc_is_malformed() {
+        let text = format!("{} {}\npc -1\n", TEXT_HEADER, VERSION);
+        assert!(matches!(
+            Snapshot::parse(text.as_bytes()),
+            Err(StateError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn memory_past_the_address_space_is_malformed() {
+        let text = format!("{} {}\nmemory {} 1,2\n", TEXT_HEADER, VERSION, usize::MAX);
+        assert!(matches!(
+            Snapshot::parse(text.as_bytes()),
+            Err(StateError::Malformed(_))
+        ));
+
+        let mut bytes = BINARY_MAGIC.to_vec();
+        for value in &[VERSION, 0, 0, 0, 0, 0, 1, usize::MAX as u64, 2] {
+            write_unsigned(&mut bytes, *value).unwrap();
+        }
+        write_signed(&mut bytes, 1).unwrap();
+        write_signed(&mut bytes, 2).unwrap();
+        assert!(matches!(
+            Snapshot::parse(&bytes),
+            Err(StateError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::io::IntcodeIo;
+use crate::{Budget, Intcode, IntcodeError, StepOutcome};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::thread;
+use std::time::Instant;
+
+const DEFAULT_CAPACITY: usize = 64;
+
+/// How the machines of a `ThreadedNetwork` are wired. Every output of a node
+/// is sent to each of its successors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Topology {
+    /// `0 -> 1 -> ... -> n-1`
+    Pipeline(usize),
+    /// A pipeline whose last node feeds back into the first.
+    Ring(usize),
+    /// Node 0 is the hub, connected both ways to every other node.
+    Star(usize),
+    Graph {
+        nodes: usize,
+        edges: Vec<(usize, usize)>,
+    },
+}
+
+impl Topology {
+    pub fn nodes(&self) -> usize {
+        match self {
+            Topology::Pipeline(nodes) | Topology::Ring(nodes) | Topology::Star(nodes) => *nodes,
+            Topology::Graph { nodes, .. } => *nodes,
+        }
+    }
+
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        match self {
+            Topology::Pipeline(nodes) => (1..*nodes).map(|to| (to - 1, to)).collect(),
+            Topology::Ring(nodes) => (0..*nodes).map(|from| (from, (from + 1) % nodes)).collect(),
+            Topology::Star(nodes) => (1..*nodes).flat_map(|n| vec![(0, n), (n, 0)]).collect(),
+            Topology::Graph { edges, .. } => edges.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TopologyError {
+    WrongMachineCount {
+        expected: usize,
+        found: usize,
+    },
+    /// An edge refers to a node the topology does not have.
+    UnknownNode(usize),
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TopologyError::WrongMachineCount { expected, found } => {
+                write!(
+                    f,
+                    "topology has {} nodes, found {} machines",
+                    expected, found
+                )
+            }
+            TopologyError::UnknownNode(node) => write!(f, "edge to unknown node {}", node),
+        }
+    }
+}
+
+impl Error for TopologyError {}
+
+/// Final state of one node after its thread finished.
+pub struct NodeResult {
+    pub intcode: Intcode,
+    pub outcome: Result<StepOutcome, IntcodeError>,
+    pub outputs: Vec<isize>,
+}
+
+struct NodeIo {
+    initial: VecDeque<isize>,
+    input: Receiver<isize>,
+    successors: Vec<SyncSender<isize>>,
+    outputs: Vec<isize>,
+    deadline: Option<Instant>,
+    /// Set when a read gave up at the deadline.
+    timed_out: bool,
+}
+
+impl IntcodeIo for NodeIo {
+    fn read(&mut self) -> Option<isize> {
+        if let Some(value) = self.initial.pop_front() {
+            return Some(value);
+        }
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return self.input.recv().ok(),
+        };
+        match self
+            .input
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            Ok(value) => Some(value),
+            Err(RecvTimeoutError::Timeout) => {
+                self.timed_out = true;
+                None
+            }
+            Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    fn write(&mut self, value: isize) {
+        self.outputs.push(value);
+        for successor in &self.successors {
+            let _ = successor.send(value);
+        }
+    }
+}
+
+/// Runs every machine on its own thread, connected by bounded channels.
+///
+/// A node blocked on input whose predecessors have all finished stops with
+/// `StepOutcome::AwaitsInput`, so a network winds down once its programs halt.
+/// Outputs sent to a node that already finished are dropped.
+///
+/// A node writing to a full channel blocks until its successor reads. In a
+/// cycle (a ring, or a graph with a loop) where every node is blocked writing
+/// at once, nobody reads and the network deadlocks. The capacity has to cover
+/// the most values a node sends before it next reads; the default is enough
+/// for programs that answer every input with a few outputs, like day 7.
+pub struct ThreadedNetwork {
+    machines: Vec<Intcode>,
+    inputs: Vec<VecDeque<isize>>,
+    edges: Vec<(usize, usize)>,
+    capacity: usize,
+    budget: Budget,
+}
+
+impl ThreadedNetwork {
+    pub fn new(machines: Vec<Intcode>, topology: &Topology) -> Result<Self, TopologyError> {
+        if machines.len() != topology.nodes() {
+            return Err(TopologyError::WrongMachineCount {
+                expected: topology.nodes(),
+                found: machines.len(),
+            });
+        }
+        let edges = topology.edges();
+        if let Some(node) = edges
+            .iter()
+            .flat_map(|&(from, to)| vec![from, to])
+            .find(|node| *node >= machines.len())
+        {
+            return Err(TopologyError::UnknownNode(node));
+        }
+
+        Ok(Self {
+            inputs: vec![VecDeque::new(); machines.len()],
+            machines,
+            edges,
+            capacity: DEFAULT_CAPACITY,
+            budget: Budget::default(),
+        })
+    }
+
+    /// Sets how many values a channel buffers before the sender blocks, see
+    /// the note on deadlocks above.
+    pub fn with_capacity(self, capacity: usize) -> Self {
+        Self { capacity, ..self }
+    }
+
+    /// Limits every machine to `budget`, so a program that never halts stops
+    /// with `StepOutcome::BudgetExhausted` and the rest of the network winds
+    /// down after it. A deadline also bounds how long a node waits for input,
+    /// so nodes all waiting on each other stop the same way; an instruction
+    /// limit alone does not. Writes blocked on a full channel are not bounded,
+    /// see the note on deadlocks above.
+    pub fn with_budget(self, budget: Budget) -> Self {
+        Self { budget, ..self }
+    }
+
+    /// Queues a value read by `node` before anything arriving from the network.
+    pub fn add_input(&mut self, node: usize, value: isize) {
+        self.inputs[node].push_back(value);
+    }
+
+    pub fn run(self) -> Vec<NodeResult> {
+        let Self {
+            machines,
+            inputs,
+            edges,
+            capacity,
+            budget,
+        } = self;
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            (0..machines.len()).map(|_| sync_channel(capacity)).unzip();
+
+        let handles: Vec<_> = machines
+            .into_iter()
+            .zip(receivers)
+            .zip(inputs)
+            .enumerate()
+            .map(|(node, ((mut intcode, input), initial))| {
+                let successors = edges
+                    .iter()
+                    .filter(|(from, _)| *from == node)
+                    .map(|(_, to)| senders[*to].clone())
+                    .collect();
+                let mut io = NodeIo {
+                    initial,
+                    input,
+                    successors,
+                    outputs: Vec::new(),
+                    deadline: budget.deadline,
+                    timed_out: false,
+                };
+
+                thread::spawn(move || {
+                    let outcome = match intcode.run_with_io_and_budget(&mut io, budget) {
+                        Ok(StepOutcome::AwaitsInput) if io.timed_out => {
+                            Ok(StepOutcome::BudgetExhausted)
+                        }
+                        outcome => outcome,
+                    };
+                    NodeResult {
+                        intcode,
+                        outcome,
+                        outputs: io.outputs,
+                    }
+                })
+            })
+            .collect();
+        drop(senders);
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("intcode thread panicked"))
+            .collect()
+    }
+}
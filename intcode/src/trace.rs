@@ -0,0 +1,362 @@
+use crate::disassembler::Operand;
+use crate::encoding::{write_signed, write_unsigned, write_values, DecodeError, Reader};
+use crate::instruction::{self, Mode, Opcode};
+use crate::{Intcode, IntcodeError, StepOutcome};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Write};
+
+const VERSION: u64 = 2;
+const MAGIC: &[u8] = b"ICTR";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoEvent {
+    Input(isize),
+    Output(isize),
+}
+
+/// One executed instruction: its raw operands, the values it read and what
+/// it wrote or exchanged with the host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: usize,
+    pub instruction: isize,
+    pub operands: Vec<Operand>,
+    pub reads: Vec<isize>,
+    /// Memory writes in order, more than one only for custom instructions.
+    pub writes: Vec<(usize, isize)>,
+    pub io: Vec<IoEvent>,
+}
+
+impl TraceEntry {
+    pub fn new(pc: usize, instruction: isize) -> Self {
+        Self {
+            pc,
+            instruction,
+            operands: Vec::new(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            io: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operands: Vec<_> = self.operands.iter().map(|o| o.to_string()).collect();
+        let mnemonic = match Opcode::try_from(self.instruction % 100) {
+            Ok(opcode) => opcode.mnemonic().to_uppercase(),
+            Err(_) => self.instruction.to_string(),
+        };
+        write!(f, "{:04}: {} {}", self.pc, mnemonic, operands.join(", "))?;
+        if !self.reads.is_empty() {
+            write!(f, " reads {:?}", self.reads)?;
+        }
+        for (address, value) in &self.writes {
+            write!(f, " [{}] <- {}", address, value)?;
+        }
+        for event in &self.io {
+            match event {
+                IoEvent::Input(value) => write!(f, " input {}", value)?,
+                IoEvent::Output(value) => write!(f, " output {}", value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Values consumed by `in` instructions, in order.
+    pub fn inputs(&self) -> Vec<isize> {
+        self.entries
+            .iter()
+            .flat_map(|entry| &entry.io)
+            .filter_map(|event| match event {
+                IoEvent::Input(value) => Some(*value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Vec<isize> {
+        self.entries
+            .iter()
+            .flat_map(|entry| &entry.io)
+            .filter_map(|event| match event {
+                IoEvent::Output(value) => Some(*value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Writes the trace in a compact binary form. Operand modes are not stored
+    /// since they are recovered from the instruction word.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_unsigned(writer, VERSION)?;
+        write_unsigned(writer, self.entries.len() as u64)?;
+
+        for entry in &self.entries {
+            write_unsigned(writer, entry.pc as u64)?;
+            write_signed(writer, entry.instruction)?;
+            write_values(writer, entry.operands.iter().map(|operand| &operand.value))?;
+            write_values(writer, entry.reads.iter())?;
+            write_unsigned(writer, entry.writes.len() as u64)?;
+            for (address, value) in &entry.writes {
+                write_unsigned(writer, *address as u64)?;
+                write_signed(writer, *value)?;
+            }
+            write_unsigned(writer, entry.io.len() as u64)?;
+            for event in &entry.io {
+                let (kind, value) = match event {
+                    IoEvent::Input(value) => (0, value),
+                    IoEvent::Output(value) => (1, value),
+                };
+                write_unsigned(writer, kind)?;
+                write_signed(writer, *value)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Trace, DecodeError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(DecodeError("not an intcode trace"));
+        }
+        let mut reader = Reader::new(&bytes[MAGIC.len()..]);
+        if reader.unsigned()? != VERSION {
+            return Err(DecodeError("unsupported trace version"));
+        }
+
+        // The count is not trusted for pre-allocation, a corrupt file could
+        // claim any number of entries.
+        let count = reader.usize()?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let pc = reader.usize()?;
+            let instruction = reader.signed()?;
+
+            let (_, mode_1, mode_2, mode_3) = instruction::decode(instruction)
+                .map_err(|_| DecodeError("invalid instruction in trace"))?;
+            let operands = reader
+                .values()?
+                .into_iter()
+                .zip([mode_1, mode_2, mode_3].iter())
+                .map(|(value, mode)| Operand { mode: *mode, value })
+                .collect();
+            let reads = reader.values()?;
+            let mut writes = Vec::new();
+            for _ in 0..reader.usize()? {
+                writes.push((reader.usize()?, reader.signed()?));
+            }
+            let mut io = Vec::new();
+            for _ in 0..reader.usize()? {
+                io.push(match reader.unsigned()? {
+                    0 => IoEvent::Input(reader.signed()?),
+                    1 => IoEvent::Output(reader.signed()?),
+                    _ => return Err(DecodeError("invalid io event in trace")),
+                });
+            }
+
+            entries.push(TraceEntry {
+                pc,
+                instruction,
+                operands,
+                reads,
+                writes,
+                io,
+            });
+        }
+
+        if !reader.is_empty() {
+            return Err(DecodeError("trailing data"));
+        }
+        Ok(Trace { entries })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Divergence {
+    /// The replayed instruction differs from the recorded one.
+    Mismatch {
+        step: usize,
+        expected: TraceEntry,
+        actual: TraceEntry,
+    },
+    /// The machine halted or blocked before the trace ended.
+    Stopped {
+        step: usize,
+        expected: TraceEntry,
+        outcome: StepOutcome,
+    },
+    Error {
+        step: usize,
+        expected: TraceEntry,
+        error: IntcodeError,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Divergence::Mismatch {
+                step,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "step {}: expected\n  {}\nbut executed\n  {}",
+                step, expected, actual
+            ),
+            Divergence::Stopped {
+                step,
+                expected,
+                outcome,
+            } => write!(
+                f,
+                "step {}: expected\n  {}\nbut machine stopped with {:?}",
+                step, expected, outcome
+            ),
+            Divergence::Error {
+                step,
+                expected,
+                error,
+            } => write!(
+                f,
+                "step {}: expected\n  {}\nbut execution failed: {}",
+                step, expected, error
+            ),
+        }
+    }
+}
+
+/// Re-runs `program` against the inputs recorded in `trace` and compares every
+/// executed instruction with the recording. Returns the number of replayed
+/// steps, or the first point where the two runs differ.
+pub fn replay(program: &[isize], trace: &Trace) -> Result<usize, Box<Divergence>> {
+    let mut intcode = Intcode::new(program);
+    for input in trace.inputs() {
+        intcode.add_input(input);
+    }
+    intcode.start_trace();
+
+    for (step, expected) in trace.entries.iter().enumerate() {
+        let outcome = match intcode.execute_single_instruction() {
+            Ok(outcome) => outcome,
+            Err(error) => {
+                return Err(Box::new(Divergence::Error {
+                    step,
+                    expected: expected.clone(),
+                    error,
+                }))
+            }
+        };
+
+        let actual = intcode.trace.as_mut().and_then(|trace| trace.entries.pop());
+        match actual {
+            Some(actual) if actual == *expected => (),
+            Some(actual) => {
+                return Err(Box::new(Divergence::Mismatch {
+                    step,
+                    expected: expected.clone(),
+                    actual,
+                }))
+            }
+            None => {
+                return Err(Box::new(Divergence::Stopped {
+                    step,
+                    expected: expected.clone(),
+                    outcome,
+                }))
+            }
+        }
+    }
+
+    Ok(trace.entries.len())
+}
+
+impl Intcode {
+    /// Starts recording every executed instruction, discarding any previous trace.
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Trace::new());
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Stops recording and returns what was recorded.
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace_entry = None;
+        self.trace.take()
+    }
+
+    pub(crate) fn trace_operand(&mut self, mode: Mode, raw: isize, read: Option<isize>) {
+        if let Some(entry) = self.trace_entry.as_mut() {
+            entry.operands.push(Operand { mode, value: raw });
+            entry.reads.extend(read);
+        }
+    }
+
+    pub(crate) fn trace_write(&mut self, address: usize, value: isize) {
+        if let Some(entry) = self.trace_entry.as_mut() {
+            entry.writes.push((address, value));
+        }
+    }
+
+    pub(crate) fn trace_io(&mut self, event: IoEvent) {
+        if let Some(entry) = self.trace_entry.as_mut() {
+            entry.io.push(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction_set::{CustomOpcode, InstructionSet, Parameter};
+
+    #[test]
+    fn records_every_write_and_io_event_of_an_instruction() {
+        let opcode = CustomOpcode::new("swap", &[Parameter::Write], |context| {
+            let address = context.argument(0).unwrap() as usize;
+            let input = context.input().unwrap();
+            context.store(address, input);
+            context.store(address + 1, -input);
+            context.output(input);
+            context.output(-input);
+            Ok(())
+        })
+        .unwrap();
+        let mut intcode = Intcode::new(&[42, 5, 99]);
+        intcode.set_instruction_set(InstructionSet::full().with(42, opcode));
+        intcode.add_input(3);
+        intcode.start_trace();
+        intcode.run().unwrap();
+
+        let trace = intcode.take_trace().unwrap();
+        let entry = &trace.entries[0];
+        assert_eq!(entry.writes, [(5, 3), (6, -3)]);
+        assert_eq!(
+            entry.io,
+            [IoEvent::Input(3), IoEvent::Output(3), IoEvent::Output(-3)]
+        );
+        assert_eq!(
+            entry.to_string(),
+            "0000: 42 [5] [5] <- 3 [6] <- -3 input 3 output 3 output -3"
+        );
+
+        let mut bytes = Vec::new();
+        trace.write(&mut bytes).unwrap();
+        assert_eq!(Trace::parse(&bytes).unwrap(), trace);
+    }
+}
@@ -0,0 +1,38 @@
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryAccess {
+    Read { value: isize },
+    Write { old_value: isize, new_value: isize },
+}
+
+/// A watched memory access, reported with the pc of the instruction that made it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub pc: usize,
+    pub address: usize,
+    pub access: MemoryAccess,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: Range<usize>,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    pub fn matches(&self, address: usize, access: &MemoryAccess) -> bool {
+        let kind_matches = match access {
+            MemoryAccess::Read { .. } => self.kind != WatchKind::Write,
+            MemoryAccess::Write { .. } => self.kind != WatchKind::Read,
+        };
+        kind_matches && self.range.contains(&address)
+    }
+}
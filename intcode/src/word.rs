@@ -0,0 +1,392 @@
+//! Intcode over other word types than `isize`.
+//!
+//! `Intcode` is fixed to `isize` since the debugging and tracing tools built on
+//! it store plain words. `Machine` runs the same instruction set over any
+//! `Word`, such as `i128` or, with the `bignum` feature, `num_bigint::BigInt`.
+
+use crate::instruction::{Mode, Opcode};
+use crate::memory::Memory;
+use crate::semantics;
+use crate::{ErrorCause, StepOutcome};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// A machine word. `Default` must be zero, the value of unwritten memory.
+pub trait Word: Clone + Default + PartialEq + PartialOrd + fmt::Debug + fmt::Display {
+    fn from_isize(value: isize) -> Self;
+    /// `None` if the value does not fit.
+    fn to_isize(&self) -> Option<isize>;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn wrapping_add(&self, other: &Self) -> Self;
+    fn wrapping_mul(&self, other: &Self) -> Self;
+}
+
+macro_rules! primitive_word {
+    ($($word:ty),*) => {
+        $(
+            impl Word for $word {
+                fn from_isize(value: isize) -> Self {
+                    value as $word
+                }
+
+                fn to_isize(&self) -> Option<isize> {
+                    use std::convert::TryFrom;
+                    isize::try_from(*self).ok()
+                }
+
+                fn checked_add(&self, other: &Self) -> Option<Self> {
+                    <$word>::checked_add(*self, *other)
+                }
+
+                fn checked_mul(&self, other: &Self) -> Option<Self> {
+                    <$word>::checked_mul(*self, *other)
+                }
+
+                fn wrapping_add(&self, other: &Self) -> Self {
+                    <$word>::wrapping_add(*self, *other)
+                }
+
+                fn wrapping_mul(&self, other: &Self) -> Self {
+                    <$word>::wrapping_mul(*self, *other)
+                }
+            }
+        )*
+    };
+}
+
+primitive_word!(i32, i64, i128, isize);
+
+#[cfg(feature = "bignum")]
+impl Word for num_bigint::BigInt {
+    fn from_isize(value: isize) -> Self {
+        value.into()
+    }
+
+    fn to_isize(&self) -> Option<isize> {
+        use num_traits::ToPrimitive;
+        ToPrimitive::to_isize(self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        self * other
+    }
+}
+
+/// An error raised by `Machine`. Same as `IntcodeError`, but the instruction
+/// is kept as a word since it may not fit an `isize`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MachineError<W> {
+    pub pc: usize,
+    pub instruction: W,
+    pub cause: ErrorCause,
+}
+
+impl<W: fmt::Display> fmt::Display for MachineError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at pc {} (instruction {})",
+            self.cause, self.pc, self.instruction
+        )
+    }
+}
+
+impl<W: fmt::Debug + fmt::Display> Error for MachineError<W> {}
+
+/// Intcode machine over any `Word`, without the debugging hooks and custom
+/// instructions of `Intcode`. Both decode and compute through the same rules,
+/// so a program behaves the same on either as long as its values fit.
+#[derive(Clone, Debug)]
+pub struct Machine<W: Word> {
+    memory: Memory<W>,
+    pc: usize,
+    relative_base: isize,
+    input: VecDeque<W>,
+    output: VecDeque<W>,
+    finished: bool,
+    awaits_input: bool,
+    checked: bool,
+}
+
+impl<W: Word> Machine<W> {
+    pub fn new(program: &[W]) -> Self {
+        Self {
+            memory: Memory::new(program),
+            pc: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            finished: false,
+            awaits_input: false,
+            checked: false,
+        }
+    }
+
+    /// See `Intcode::enable_overflow_checks`.
+    pub fn enable_overflow_checks(&mut self) {
+        self.checked = true;
+    }
+
+    pub fn disable_overflow_checks(&mut self) {
+        self.checked = false;
+    }
+
+    pub fn run(&mut self) -> Result<StepOutcome, MachineError<W>> {
+        loop {
+            match self.execute_single_instruction()? {
+                StepOutcome::Executed => continue,
+                outcome => return Ok(outcome),
+            }
+        }
+    }
+
+    pub fn execute_single_instruction(&mut self) -> Result<StepOutcome, MachineError<W>> {
+        if self.finished {
+            return Ok(StepOutcome::Finished);
+        }
+        if self.awaits_input {
+            return Ok(StepOutcome::AwaitsInput);
+        }
+
+        let pc = self.pc;
+        let instruction = self.read_from_memory(pc);
+        match self.step(&instruction) {
+            Ok(()) if self.finished => Ok(StepOutcome::Finished),
+            Ok(()) if self.awaits_input => Ok(StepOutcome::AwaitsInput),
+            Ok(()) => Ok(StepOutcome::Executed),
+            Err(cause) => {
+                self.pc = pc;
+                Err(MachineError {
+                    pc,
+                    instruction,
+                    cause,
+                })
+            }
+        }
+    }
+
+    fn step(&mut self, instruction: &W) -> Result<(), ErrorCause> {
+        let (opcode, mode_1, mode_2, mode_3) = semantics::decode(instruction)?;
+        let opcode = Opcode::try_from(opcode)?;
+        self.pc += 1;
+
+        match opcode {
+            Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => {
+                let a = self.read(mode_1)?;
+                let b = self.read(mode_2)?;
+                let result = semantics::evaluate(opcode, &a, &b, self.checked)?;
+                self.write(mode_3, result)
+            }
+            Opcode::In => {
+                let address = self.write_address(mode_1)?;
+                match self.input.pop_front() {
+                    Some(input) => self.store(address, input),
+                    None => {
+                        self.pc -= 2;
+                        self.awaits_input = true;
+                    }
+                }
+                Ok(())
+            }
+            Opcode::Out => {
+                let output = self.read(mode_1)?;
+                self.output.push_back(output);
+                Ok(())
+            }
+            Opcode::Jnz | Opcode::Jz => {
+                let condition = self.read(mode_1)?;
+                let target = self.read(mode_2)?;
+                if semantics::jumps(opcode, &condition) {
+                    self.pc = semantics::to_address(&target)?;
+                }
+                Ok(())
+            }
+            Opcode::Arb => {
+                let offset = self.read(mode_1)?;
+                self.relative_base =
+                    semantics::relative_address(self.relative_base, &offset, self.checked)?;
+                Ok(())
+            }
+            Opcode::Hlt => {
+                self.finished = true;
+                Ok(())
+            }
+        }
+    }
+
+    fn read(&mut self, mode: Mode) -> Result<W, ErrorCause> {
+        let raw = self.read_from_memory(self.pc);
+        self.pc += 1;
+
+        match mode {
+            Mode::Immediate => Ok(raw),
+            _ => Ok(self.read_from_memory(semantics::address(
+                mode,
+                &raw,
+                self.relative_base,
+                self.checked,
+            )?)),
+        }
+    }
+
+    fn write_address(&mut self, mode: Mode) -> Result<usize, ErrorCause> {
+        let raw = self.read_from_memory(self.pc);
+        self.pc += 1;
+
+        semantics::address(mode, &raw, self.relative_base, self.checked)
+    }
+
+    fn write(&mut self, mode: Mode, value: W) -> Result<(), ErrorCause> {
+        let address = self.write_address(mode)?;
+        self.store(address, value);
+        Ok(())
+    }
+
+    fn store(&mut self, address: usize, value: W) {
+        self.memory.set(address, value);
+    }
+
+    pub fn read_from_memory(&self, address: usize) -> W {
+        self.memory.get(address)
+    }
+
+    pub fn write_to_memory(&mut self, address: usize, value: W) {
+        self.store(address, value);
+    }
+
+    pub fn add_input(&mut self, value: W) {
+        self.awaits_input = false;
+        self.input.push_back(value);
+    }
+
+    pub fn get_output(&self) -> &VecDeque<W> {
+        &self.output
+    }
+
+    pub fn get_first_output(&mut self) -> Option<W> {
+        self.output.pop_front()
+    }
+
+    pub fn get_last_output(&mut self) -> Option<W> {
+        self.output.pop_back()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn awaits_input(&self) -> bool {
+        self.awaits_input
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Intcode, IntcodeError};
+
+    /// Adds 1 + 1, then applies `opcode` in immediate mode to `a` and `b`.
+    fn program<W: Word>(opcode: isize, a: W, b: W) -> Vec<W> {
+        let mut program: Vec<_> = [1101, 1, 1, 0, 1100 + opcode]
+            .iter()
+            .map(|word| W::from_isize(*word))
+            .collect();
+        program.extend(vec![a, b, W::from_isize(0), W::from_isize(99)]);
+        program
+    }
+
+    fn overflow(opcode: isize) {
+        let mut machine = Machine::new(&program(opcode, i32::MAX, 2));
+        machine.enable_overflow_checks();
+
+        let error = machine.run().unwrap_err();
+        assert_eq!(
+            error,
+            MachineError {
+                pc: 4,
+                instruction: 1100 + opcode as i32,
+                cause: ErrorCause::Overflow,
+            }
+        );
+        assert_eq!(machine.pc(), 4);
+
+        let mut intcode = Intcode::new(&program(opcode, isize::MAX, 2));
+        intcode.enable_overflow_checks();
+        assert_eq!(
+            intcode.run().unwrap_err(),
+            IntcodeError::new(4, 1100 + opcode, ErrorCause::Overflow)
+        );
+    }
+
+    #[test]
+    fn checked_add_reports_overflow_at_its_pc() {
+        overflow(1);
+    }
+
+    #[test]
+    fn checked_mul_reports_overflow_at_its_pc() {
+        overflow(2);
+    }
+
+    #[test]
+    fn boost_matches_intcode() {
+        let program = crate::Program::load("../day09/input").unwrap();
+        let mut machine = Machine::new(&program);
+        let mut intcode = Intcode::new(&program);
+        machine.add_input(1);
+        intcode.add_input(1);
+
+        loop {
+            let outcome = intcode.execute_single_instruction().unwrap();
+            assert_eq!(machine.execute_single_instruction().unwrap(), outcome);
+            assert_eq!(machine.pc(), intcode.pc());
+            assert_eq!(machine.get_output(), intcode.get_output());
+            if outcome != StepOutcome::Executed {
+                break;
+            }
+        }
+        assert_eq!(machine.get_last_output(), Some(4_288_078_517));
+    }
+
+    #[test]
+    fn unchecked_arithmetic_wraps() {
+        let mut machine = Machine::new(&program(1, i32::MAX, 1));
+        assert_eq!(machine.run().unwrap(), StepOutcome::Finished);
+        assert_eq!(machine.read_from_memory(0), i32::MIN);
+    }
+
+    #[test]
+    fn wide_instruction_is_out_of_range() {
+        let mut machine = Machine::new(&[i128::MAX]);
+        let error = machine.run().unwrap_err();
+        assert_eq!(error.instruction, i128::MAX);
+        assert_eq!(error.cause, ErrorCause::InstructionOutOfRange);
+    }
+
+    #[test]
+    fn wide_address_is_out_of_range() {
+        let mut machine = Machine::new(&[4, i128::MIN, 99]);
+        let error = machine.run().unwrap_err();
+        assert_eq!(error.pc, 0);
+        assert_eq!(error.cause, ErrorCause::AddressOutOfRange);
+    }
+}
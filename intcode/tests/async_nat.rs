@@ -0,0 +1,169 @@
+use futures::channel::mpsc;
+use futures::executor::{self, LocalPool};
+use futures::task::LocalSpawnExt;
+use futures::{sink, stream, StreamExt};
+use intcode::asynchronous::{self, AsyncIntcode};
+use intcode::network::{Monitor, Nat, Packet, NAT_ADDRESS};
+use intcode::{Intcode, StepOutcome};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::rc::Rc;
+
+const COMPUTERS: usize = 50;
+
+/// Packet queues between the machines, with the library's `Nat` listening on
+/// address 255.
+struct Network {
+    queues: Vec<VecDeque<isize>>,
+    empty_reads: Vec<usize>,
+    nat: Nat,
+}
+
+impl Network {
+    fn new() -> Self {
+        Self {
+            queues: (0..COMPUTERS).map(|a| vec![a as isize].into()).collect(),
+            empty_reads: vec![0; COMPUTERS],
+            nat: Nat::new(0),
+        }
+    }
+
+    fn receive(&mut self, address: usize) -> isize {
+        match self.queues[address].pop_front() {
+            Some(value) => {
+                self.empty_reads[address] = 0;
+                value
+            }
+            None => {
+                self.empty_reads[address] += 1;
+                -1
+            }
+        }
+    }
+
+    fn send(&mut self, packet: Packet) {
+        if packet.address == NAT_ADDRESS {
+            self.nat.receive(packet);
+        } else {
+            self.queues[packet.address as usize].extend(&[packet.x, packet.y]);
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty) && self.empty_reads.iter().all(|r| *r >= 2)
+    }
+}
+
+#[test]
+fn nat_sends_the_same_y_twice_in_a_row() {
+    let program = intcode::Program::load("../day23/input").unwrap();
+
+    let network = Rc::new(RefCell::new(Network::new()));
+    let mut pool = LocalPool::new();
+    let spawner = pool.spawner();
+
+    for address in 0..COMPUTERS {
+        let input = stream::repeat(()).map({
+            let network = network.clone();
+            move |_| network.borrow_mut().receive(address)
+        });
+        let output = Box::pin(sink::unfold(Vec::new(), {
+            let network = network.clone();
+            move |mut packet: Vec<isize>, value| {
+                let network = network.clone();
+                async move {
+                    packet.push(value);
+                    if packet.len() == 3 {
+                        let packet = Packet::new(packet[0], packet[1], packet[2]);
+                        network.borrow_mut().send(packet);
+                        return Ok::<_, Infallible>(Vec::new());
+                    }
+                    Ok(packet)
+                }
+            }
+        }));
+
+        let mut computer = AsyncIntcode::new(Intcode::new(&program));
+        spawner
+            .spawn_local(async move {
+                computer.run(input, output).await.unwrap();
+            })
+            .unwrap();
+    }
+
+    let nat = async {
+        let mut last_y = None;
+        loop {
+            asynchronous::yield_now().await;
+
+            let mut network = network.borrow_mut();
+            if !network.is_idle() {
+                continue;
+            }
+            if let Some(packet) = network.nat.wake() {
+                if last_y == Some(packet.y) {
+                    return packet.y;
+                }
+                last_y = Some(packet.y);
+                network.send(packet);
+            }
+        }
+    };
+
+    assert_eq!(pool.run_until(nat), 15_742);
+}
+
+#[test]
+fn in_suspends_until_the_channel_has_a_value() {
+    // in [9], out [9], in [9], out [9], hlt
+    let program = [3, 9, 4, 9, 3, 9, 4, 9, 99, 0];
+    let (sender, receiver) = mpsc::unbounded();
+    let outputs = Rc::new(RefCell::new(Vec::new()));
+    let outcome = Rc::new(RefCell::new(None));
+
+    let mut pool = LocalPool::new();
+    pool.spawner()
+        .spawn_local({
+            let outputs = outputs.clone();
+            let outcome = outcome.clone();
+            async move {
+                let output = Box::pin(sink::unfold((), move |_, value| {
+                    outputs.borrow_mut().push(value);
+                    async { Ok::<_, Infallible>(()) }
+                }));
+                let mut computer = AsyncIntcode::new(Intcode::new(&program));
+                *outcome.borrow_mut() = Some(computer.run(receiver, output).await.unwrap());
+            }
+        })
+        .unwrap();
+
+    pool.run_until_stalled();
+    assert!(outputs.borrow().is_empty());
+    assert_eq!(*outcome.borrow(), None);
+
+    sender.unbounded_send(7).unwrap();
+    pool.run_until_stalled();
+    assert_eq!(*outputs.borrow(), vec![7]);
+    assert_eq!(*outcome.borrow(), None);
+
+    sender.unbounded_send(8).unwrap();
+    pool.run_until_stalled();
+    assert_eq!(*outputs.borrow(), vec![7, 8]);
+    assert_eq!(*outcome.borrow(), Some(StepOutcome::Finished));
+}
+
+#[test]
+fn queued_input_is_read_before_the_stream() {
+    // in [9], out [9], in [9], out [9], hlt
+    let program = [3, 9, 4, 9, 3, 9, 4, 9, 99, 0];
+    let mut intcode = Intcode::new(&program);
+    intcode.add_input(5);
+    let mut computer = AsyncIntcode::new(intcode);
+
+    let mut outputs = Vec::new();
+    let outcome = executor::block_on(computer.run(stream::iter(vec![6, 7]), &mut outputs));
+
+    assert_eq!(outcome.unwrap(), StepOutcome::Finished);
+    assert_eq!(outputs, vec![5, 6]);
+}